@@ -1,21 +1,69 @@
 use std::{
     collections::HashMap,
+    os::unix::net::UnixListener,
+    path::PathBuf,
     process::Command,
-    sync::{Arc, Mutex},
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
 };
 
-use eframe::{egui_glow, glow};
+use eframe::glow;
 use egui::{
-    Align, Color32, Frame, Grid, Image, ImageButton, Label, Layout, Pos2, Response,
-    Rounding, Sense, Stroke, TextEdit, Vec2,
+    Align, Button, Color32, ColorImage, EventFilter, Frame, Grid, Image, ImageButton, Key, Label,
+    Layout, Modifiers, Pos2, Rect, Response, Rounding, Sense, Stroke, TextEdit, Vec2,
+    ViewportBuilder, ViewportCommand, ViewportId,
 };
 
 use crate::{
-    color::{rgb_to_cymk, rgb_to_hsl, Color},
-    gradient::{Gradient, GradientType},
-    theme,
+    capture,
+    color::{max_by_name, rgb_to_cymk, rgb_to_hsl, Color, Model},
+    dbus, expression,
+    gradient::{Dither, GlowPainter, GradientPainter, GradientType, RenderOptions, Renderer},
+    hyprland::{self, Hyprland, Preview},
+    image_view::ImageView,
+    ipc::{self, Request, Service},
+    loupe::{Loupe, LoupeEvent},
+    mesh::MeshPainter,
+    palette::{self, Format, Method, Swatch},
+    paths, portal, portal_backend,
+    sample::{Reduce, Sampling},
+    scheme::{self, Scheme, Slot, Terminal},
+    settings::Settings,
+    shape::{Part, Plane, Shape},
+    templates::{Template, Templates, Values},
+    theme::{self, Theme, ThemeWatcher},
+    tray::Tray,
+    wallpaper,
+    wgpu_gradient::WgpuPainter,
 };
 
+const TABS: [&str; 5] = ["RGB", "HSV", "HSL", "CMYK", "Values"];
+const TAB_KEYS: [Key; 5] = [Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5];
+// Picked colors the tray menu offers again.
+const HISTORY_LEN: usize = 10;
+// How long the compositor gets to take the window off screen before a
+// screenshot.
+const HIDE_DELAY: Duration = Duration::from_millis(200);
+pub const SLIDER_LABELS: [&str; 12] = ["r", "g", "b", "h", "s", "v", "sl", "l", "c", "m", "y", "k"];
+// The compact popup's window, see `draw_compact`. Hyprland rules can match
+// its title.
+const COMPACT_TITLE: &str = "WayColor Compact";
+const COMPACT_SIZE: Vec2 = Vec2::new(240.0, 280.0);
+
+// A loaded image and its file name, or `None` if the dialog was dismissed.
+type ImageLoad = Result<Option<(String, ColorImage)>, String>;
+// The wallpaper's file name and image with the palette extracted from it.
+type WallpaperLoad = Result<(String, ColorImage, Vec<Swatch>), String>;
+// Where a file was written, or `None` if the dialog was dismissed.
+type FileSave = Result<Option<PathBuf>, String>;
+
+// The compact popup's state while it's open. It only closes on focus loss
+// once it had focus, a new window starts out without.
+#[derive(Default)]
+struct Compact {
+    focused: bool,
+}
+
 pub struct App {
     tab: String,
     color: Color,
@@ -23,27 +71,130 @@ pub struct App {
     spacing: f32,
     gradient_width: f32,
     gradient_height: f32,
+    gradient_margin: f32,
+    controls_height: f32,
     main_handle_radius: f32,
     main_handle_stroke: f32,
     slider_handle_stroke: f32,
     slider_height: f32,
     slider_margin: f32,
     gradient_click: bool,
-    gradient: Arc<Mutex<Gradient>>,
+    drag_position: Option<Pos2>,
+    drag_part: Part,
+    shape: Shape,
+    fine_drag_sensitivity: f32,
+    snap_increments: HashMap<String, u16>,
+    renderer: Renderer,
+    render_options: RenderOptions,
+    gpu_painter: Option<Box<dyn GradientPainter>>,
+    render_error: Option<String>,
+    render_error_seen: bool,
     slider_clicks: HashMap<String, bool>,
-    slider_gradients: HashMap<String, Arc<Mutex<Gradient>>>,
     slider_texts: HashMap<String, String>,
+    slider_errors: HashMap<String, String>,
+    // HSL or CMYK values as last set from their sliders, kept while the
+    // color they made is current. Going back through rounded HSV would
+    // shift the other channels, and black has no C, M or Y to go back to.
+    model_values: HashMap<String, u16>,
+    model_values_hex: String,
+    theme: Theme,
+    theme_watcher: Option<ThemeWatcher>,
+    capture: Option<Receiver<Result<ColorImage, String>>>,
+    capture_error: Option<String>,
+    picker_error: Option<String>,
+    // Failures of the tray, D-Bus and portal services, each shown once.
+    service_errors: Vec<String>,
+    loupe: Option<Loupe>,
+    sampling: Sampling,
+    image_view: Option<ImageView>,
+    image_open: bool,
+    image_load: Option<Receiver<ImageLoad>>,
+    image_error: Option<String>,
+    palette: Vec<Swatch>,
+    palette_extract: Option<Receiver<Vec<Swatch>>>,
+    palette_open: bool,
+    palette_method: Method,
+    palette_count: usize,
+    palette_format: Format,
+    file_save: Option<Receiver<FileSave>>,
+    file_status: Option<Result<String, String>>,
+    wallpaper_path: String,
+    wallpaper_load: Option<Receiver<WallpaperLoad>>,
+    scheme: Option<Scheme>,
+    scheme_dark: bool,
+    scheme_background: Option<usize>,
+    scheme_open: bool,
+    scheme_slot: Option<Slot>,
+    scheme_terminal: Terminal,
+    wallpaper_error: Option<String>,
+    templates: Templates,
+    templates_open: bool,
+    templates_status: Option<Result<String, String>>,
+    hyprland: Option<Hyprland>,
+    hyprland_keyword: String,
+    hyprland_preview: Option<Preview>,
+    hyprland_error: Option<String>,
+    requests: Receiver<Request>,
+    // For services started later, which send their requests here too.
+    sender: Sender<Request>,
+    pick_requests: Vec<Request>,
+    hidden: bool,
+    dbus: Option<dbus::Service>,
+    portal_backend: Option<portal_backend::Backend>,
+    history: Vec<Color>,
+    tray: Option<Tray>,
+    compact: Option<Compact>,
 }
 
 impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let gl = cc
-            .gl
-            .as_ref()
-            .expect("You need to run eframe with the glow backend");
-
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        listener: Option<UnixListener>,
+        startup: Vec<ipc::Command>,
+    ) -> Self {
+        let (sender, requests) = mpsc::channel();
+        for command in startup {
+            let _ = sender.send(Request::local(command));
+        }
+        if let Some(listener) = listener {
+            ipc::serve(listener, cc.egui_ctx.clone(), sender.clone());
+        }
         let color = Color::from_rgb(22, 22, 33);
-        let slider_labels = ["r", "g", "b", "h", "s", "v"];
+        let settings = Settings::load();
+        let gtypes: Vec<_> = Shape::ALL
+            .map(GradientType::Gradient)
+            .into_iter()
+            .chain(SLIDER_LABELS.map(|n| GradientType::Slider(n.to_string())))
+            .collect();
+        let mut render_error = None;
+        let gpu_painter: Option<Box<dyn GradientPainter>> = if let Some(gl) = &cc.gl {
+            match GlowPainter::new(gl, &gtypes) {
+                Ok(painter) => Some(Box::new(painter)),
+                Err(err) => {
+                    render_error = Some(err.to_string());
+                    None
+                }
+            }
+        } else {
+            match cc
+                .wgpu_render_state
+                .as_ref()
+                .map(|rs| WgpuPainter::new(rs, &gtypes))
+            {
+                Some(Ok(painter)) => Some(Box::new(painter)),
+                Some(Err(err)) => {
+                    render_error = Some(err);
+                    None
+                }
+                None => None,
+            }
+        };
+        let renderer = match std::env::var("WAYCOLOR_RENDERER").as_deref() {
+            _ if gpu_painter.is_none() => Renderer::Mesh,
+            Ok("mesh") => Renderer::Mesh,
+            _ => Renderer::Gpu,
+        };
+
         Self {
             tab: String::from("HSV"),
             hex: color.hex.clone(),
@@ -51,56 +202,128 @@ impl App {
             spacing: 5.0,
             gradient_width: 380.0,
             gradient_height: 270.0,
+            gradient_margin: 10.0,
+            controls_height: 0.0,
             main_handle_radius: 13.0,
             main_handle_stroke: 2.5,
             slider_handle_stroke: 2.0,
             slider_height: 20.0,
             slider_margin: 12.0,
-            gradient: Arc::new(Mutex::new(Gradient::new(gl, GradientType::Gradient))),
+            renderer,
+            render_options: RenderOptions::default(),
+            gpu_painter,
+            render_error,
+            render_error_seen: false,
             gradient_click: false,
-            slider_clicks: slider_labels
+            drag_position: None,
+            drag_part: Part::Plane,
+            shape: Shape::Plane(Plane::SV),
+            fine_drag_sensitivity: settings.fine_drag_sensitivity,
+            snap_increments: settings.snap_increments,
+            slider_clicks: SLIDER_LABELS
                 .iter()
                 .map(|n| (n.to_string(), false))
                 .collect(),
-            slider_gradients: slider_labels
-                .iter()
-                .map(|n| {
-                    (
-                        n.to_string(),
-                        Arc::new(Mutex::new(Gradient::new(
-                            gl,
-                            GradientType::Slider(n.to_string()),
-                        ))),
-                    )
-                })
-                .collect(),
-            slider_texts: slider_labels
+            slider_texts: SLIDER_LABELS
                 .iter()
                 .map(|n| (n.to_string(), format!("{:.0}", color.value_by_name(n))))
                 .collect(),
+            slider_errors: HashMap::new(),
+            model_values: HashMap::new(),
+            model_values_hex: String::new(),
+            theme: theme::THEME,
+            theme_watcher: ThemeWatcher::start(&cc.egui_ctx),
+            capture: None,
+            capture_error: None,
+            picker_error: None,
+            service_errors: Vec::new(),
+            loupe: None,
+            sampling: Sampling::default(),
+            image_view: None,
+            image_open: false,
+            image_load: None,
+            image_error: None,
+            palette: Vec::new(),
+            palette_extract: None,
+            palette_open: false,
+            palette_method: Method::default(),
+            palette_count: 8,
+            palette_format: Format::default(),
+            file_save: None,
+            file_status: None,
+            wallpaper_path: String::new(),
+            wallpaper_load: None,
+            scheme: None,
+            scheme_dark: true,
+            scheme_background: None,
+            scheme_open: false,
+            scheme_slot: None,
+            scheme_terminal: Terminal::default(),
+            wallpaper_error: None,
+            templates: Templates::load(),
+            templates_open: false,
+            templates_status: None,
+            hyprland: Hyprland::from_env(),
+            hyprland_keyword: String::from(hyprland::DEFAULT_KEYWORD),
+            hyprland_preview: None,
+            hyprland_error: None,
+            requests,
+            sender,
+            pick_requests: Vec::new(),
+            hidden: false,
+            dbus: None,
+            portal_backend: None,
+            history: Vec::new(),
+            tray: None,
+            compact: None,
         }
     }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(theme) = self.theme_watcher.as_ref().and_then(|w| w.poll()) {
+            self.theme = theme;
+        }
         let old = ctx.style().visuals.clone();
-        ctx.set_visuals(theme::THEME.visuals(old));
+        ctx.set_visuals(self.theme.visuals(old));
+        self.poll_requests(ctx);
+        self.poll_render_error();
+        self.poll_capture(ctx);
+        if self.compact.is_some() {
+            self.draw_compact(ctx);
+        }
+        if self.loupe.is_some() {
+            self.draw_loupe(ctx);
+            return;
+        }
+        let dropped = ctx.input(|i| i.raw.dropped_files.first().and_then(|f| f.path.clone()));
+        if let Some(path) = dropped {
+            self.start_image_load(ctx, Some(path));
+        }
+        self.poll_image_load(ctx);
+        if !ctx.wants_keyboard_input() {
+            for (tab, key) in TABS.iter().zip(TAB_KEYS) {
+                if ctx.input(|i| i.key_pressed(key)) {
+                    self.set_open(tab.to_string(), true);
+                }
+            }
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
-            let hue = Color::from_hsv(self.color.h, 100, 100);
+            self.update_layout(ui);
             ui.set_max_width(self.gradient_width);
             ui.spacing_mut().item_spacing = Vec2::new(self.spacing, self.spacing);
             self.draw_gradient(
                 ui,
-                GradientType::Gradient,
+                GradientType::Gradient(self.shape),
                 Vec2::new(self.gradient_width, self.gradient_height),
-                &hue,
+                &self.color.clone(),
             );
+            let gradient_bottom = ui.min_rect().bottom();
 
             ui.spacing_mut().item_spacing = Vec2::new(self.spacing, self.spacing) * 2.0;
             ui.horizontal(|ui| {
-                ["RGB", "HSV", "Values"]
-                    .iter()
+                TABS.iter()
                     .for_each(|label| self.draw_tab_toggle(ui, label.to_string()));
             });
 
@@ -110,27 +333,83 @@ impl eframe::App for App {
             } else {
                 self.draw_values(ui);
             }
+            let sliders_bottom = ui.min_rect().bottom();
 
-            ui.with_layout(Layout::left_to_right(Align::BOTTOM), |ui| {
-                self.draw_footer(ui);
-            });
+            let footer = ui
+                .with_layout(Layout::left_to_right(Align::BOTTOM), |ui| {
+                    self.draw_footer(ui);
+                })
+                .response
+                .rect;
+            let controls_height = sliders_bottom - gradient_bottom + footer.height();
+            if (controls_height - self.controls_height).abs() > 0.5 {
+                self.controls_height = controls_height;
+                ui.ctx().request_repaint();
+            }
         });
+        self.draw_image_window(ctx);
+        self.poll_file_save();
+        self.poll_palette_extract();
+        self.poll_wallpaper_load(ctx);
+        self.draw_palette_window(ctx);
+        self.draw_scheme_window(ctx);
+        self.draw_templates_window(ctx);
+        let (color, palette, scheme) = (&self.color, &self.palette, self.scheme.as_ref());
+        self.templates
+            .poll(ctx, || Values::new(color, palette, scheme));
+        if let Some(preview) = self.hyprland_preview.as_mut() {
+            preview.update(&self.color);
+            if let Some(error) = preview.take_error() {
+                self.hyprland_error = Some(error);
+            }
+            if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(Key::Escape)) {
+                self.cancel_hyprland_preview();
+            }
+        }
+        let results = [
+            self.dbus.as_mut().map(|dbus| dbus.notify(&self.color)),
+            self.tray
+                .as_mut()
+                .map(|tray| tray.update(&self.color, self.hidden, &self.history)),
+            self.tray.as_ref().and_then(Tray::take_error).map(Err),
+            self.portal_backend
+                .as_ref()
+                .and_then(portal_backend::Backend::take_error)
+                .map(Err),
+        ];
+        for error in results.into_iter().flatten().filter_map(Result::err) {
+            self.report_service_error(error);
+        }
     }
 
     fn on_exit(&mut self, gl: Option<&glow::Context>) {
-        if let Some(gl) = gl {
-            self.gradient.lock().unwrap().destroy(gl);
-            for (_, s) in self.slider_gradients.iter_mut() {
-                s.lock().unwrap().destroy(gl);
-            }
+        self.cancel_hyprland_preview();
+        if let (Some(gl), Some(painter)) = (gl, &self.gpu_painter) {
+            painter.destroy(gl);
         }
     }
 }
 
 impl App {
+    // Everything below the main gradient keeps its natural height, as
+    // measured in the previous frame, so the gradient takes whatever is
+    // left. Sizes are snapped to whole physical pixels to keep the GL
+    // viewport aligned at fractional scale factors.
+    fn update_layout(&mut self, ui: &egui::Ui) {
+        let available = ui.available_size();
+        let size = Vec2::new(
+            available.x - self.gradient_margin * 2.0,
+            available.y - self.gradient_margin * 2.0 - self.controls_height,
+        )
+        .max(Vec2::ZERO);
+        let size = snap_to_pixels(ui, size);
+        self.gradient_width = size.x;
+        self.gradient_height = size.y;
+    }
+
     fn draw_tab_toggle(&mut self, ui: &mut egui::Ui, label: String) {
         let mut is_open = self.tab.contains(&label);
-        ui.toggle_value(&mut is_open, &format!("  {label}  "));
+        ui.toggle_value(&mut is_open, format!(" {label} "));
         self.set_open(label, is_open);
     }
 
@@ -142,13 +421,7 @@ impl App {
                     .num_columns(3)
                     .min_col_width(0.0)
                     .show(ui, |ui| {
-                        match self.tab.as_str() {
-                            "RGB" => vec!["r", "g", "b"],
-                            "HSV" => vec!["h", "s", "v"],
-                            _ => vec![],
-                        }
-                        .iter()
-                        .for_each(|label| {
+                        tab_sliders(&self.tab).iter().for_each(|label| {
                             self.draw_slider(ui, label.to_string());
                             ui.end_row();
                         });
@@ -163,13 +436,16 @@ impl App {
             Vec2::new(label_width, 20.0),
             Label::new(label.chars().next().unwrap().to_uppercase().to_string()),
         );
-        let size = Vec2::new(
-            self.gradient_width
-                - self.slider_margin * 2.0
-                - self.spacing * 2.0
-                - text_edit_width
-                - label_width,
-            self.slider_height,
+        let size = snap_to_pixels(
+            ui,
+            Vec2::new(
+                self.gradient_width
+                    - self.slider_margin * 2.0
+                    - self.spacing * 2.0
+                    - text_edit_width
+                    - label_width,
+                self.slider_height,
+            ),
         );
         self.draw_gradient(
             ui,
@@ -177,43 +453,57 @@ impl App {
             size,
             &self.color.clone(),
         );
-        if ui
-            .add_sized(
-                Vec2::new(text_edit_width, 20.0),
-                TextEdit::singleline(self.slider_texts.get_mut(&label).unwrap())
-                    .horizontal_align(Align::RIGHT)
-                    .vertical_align(Align::Center),
-            )
-            .changed()
-        {
-            self.on_slider_text_changed(label);
-        };
+        let response = ui.add_sized(
+            Vec2::new(text_edit_width, 20.0),
+            TextEdit::singleline(self.slider_texts.get_mut(&label).unwrap())
+                .horizontal_align(Align::RIGHT)
+                .vertical_align(Align::Center),
+        );
+        if let Some(error) = self.slider_errors.get(&label) {
+            ui.painter().rect_stroke(
+                response.rect,
+                2.0,
+                Stroke::new(1.0, ui.visuals().error_fg_color),
+            );
+            response.clone().on_hover_text(error);
+        }
+        if response.changed() {
+            self.on_slider_text_changed(label.clone());
+        }
+        if response.lost_focus() {
+            self.on_slider_text_committed(label);
+        }
     }
 
     fn draw_gradient(&mut self, ui: &mut egui::Ui, gtype: GradientType, size: Vec2, hue: &Color) {
         let response = match &gtype {
-            GradientType::Gradient => self.draw_main_gradient(ui, size, hue),
+            GradientType::Gradient(_) => self.draw_main_gradient(ui, size, hue),
             GradientType::Slider(stype) => self.draw_slider_gradient(ui, stype.clone(), size, hue),
         };
         self.handle_gradient_scroll(ui, &response, &gtype);
+        self.handle_gradient_keys(ui, &response, &gtype);
         self.handle_gradient_click(&response, &gtype);
     }
 
-    fn draw_main_gradient(&mut self, ui: &mut egui::Ui, size: Vec2, hue: &Color) -> Response {
-        let response = self.draw_gradient_frame(ui, size, hue, self.gradient.clone());
+    fn draw_main_gradient(&mut self, ui: &mut egui::Ui, size: Vec2, color: &Color) -> Response {
+        let response =
+            self.draw_gradient_frame(ui, GradientType::Gradient(self.shape), size, color);
         let rect = response.rect;
-        let position = Pos2 {
-            x: rect.min.x + rect.width() * self.color.float_by_name("s"),
-            y: rect.min.y + rect.height() - rect.height() * self.color.float_by_name("v"),
-        };
-        self.draw_gradient_handle(
-            ui,
-            position,
-            &self.color.clone(),
-            &self.color.inv(),
-            self.main_handle_radius,
-            self.main_handle_stroke,
-        );
+        self.draw_focus_outline(ui, &response);
+        self.draw_render_error(ui, rect);
+        response.context_menu(|ui| self.draw_gradient_menu(ui));
+        let handles = self.shape.handles(rect, color);
+        for (i, (position, color)) in handles.iter().enumerate().rev() {
+            let (radius, stroke) = if i == 0 {
+                (self.main_handle_radius, self.main_handle_stroke)
+            } else {
+                (
+                    (self.slider_height - self.slider_handle_stroke) * 0.5,
+                    self.slider_handle_stroke,
+                )
+            };
+            self.draw_gradient_handle(ui, *position, color, &color.inv(), radius, stroke);
+        }
         response
     }
 
@@ -224,8 +514,8 @@ impl App {
         size: Vec2,
         hue: &Color,
     ) -> Response {
-        let gradient = self.slider_gradients.get(&stype).unwrap().clone();
-        let response = self.draw_gradient_frame(ui, size, hue, gradient);
+        let response = self.draw_gradient_frame(ui, GradientType::Slider(stype.clone()), size, hue);
+        self.draw_focus_outline(ui, &response);
         let radius = (self.slider_height - self.slider_handle_stroke) * 0.5;
         let color = if stype == "h" {
             Color::from_hsv(self.color.h, 100, 100)
@@ -233,8 +523,9 @@ impl App {
             self.color.clone()
         };
         let rect = response.rect;
+        let t = self.model_value(&stype) as f32 / max_by_name(&stype) as f32;
         let position = Pos2 {
-            x: rect.min.x + rect.width() * color.float_by_name(&stype),
+            x: rect.min.x + rect.width() * t,
             y: rect.min.y + rect.height() * 0.5,
         };
         self.draw_gradient_handle(
@@ -251,18 +542,24 @@ impl App {
     fn draw_gradient_frame(
         &mut self,
         ui: &mut egui::Ui,
+        gtype: GradientType,
         size: Vec2,
         hue: &Color,
-        gradient: Arc<Mutex<Gradient>>,
     ) -> Response {
+        // The round shapes don't cover their whole rect.
+        let background = match gtype {
+            GradientType::Gradient(Shape::Triangle | Shape::Wheel) => Color32::TRANSPARENT,
+            GradientType::Gradient(Shape::Plane(plane)) if plane.strip => Color32::TRANSPARENT,
+            _ => Color32::from_black_alpha(255),
+        };
         egui::Frame::default()
-            .stroke(Stroke::new(1.0, Color32::from_black_alpha(255)))
-            .fill(Color32::from_black_alpha(255))
+            .stroke(Stroke::new(1.0, background))
+            .fill(background)
             .inner_margin(0.0)
-            .outer_margin(10.0)
+            .outer_margin(self.gradient_margin)
             .rounding(Rounding::same(0.0))
             .show(ui, |ui| {
-                self.draw_gradient_canvas(ui, size, hue.clone(), gradient)
+                self.draw_gradient_canvas(ui, gtype, size, hue.clone())
             })
             .inner
     }
@@ -270,30 +567,104 @@ impl App {
     fn draw_gradient_canvas(
         &mut self,
         ui: &mut egui::Ui,
+        gtype: GradientType,
         size: Vec2,
         hue: Color,
-        gradient: Arc<Mutex<Gradient>>,
     ) -> Response {
         let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
-        let callback = egui::PaintCallback {
-            rect,
-            callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |_info, painter| {
-                gradient.lock().unwrap().paint(painter.gl(), hue.clone());
-            })),
+        let painter: &dyn GradientPainter = match &self.gpu_painter {
+            Some(painter) if self.renderer == Renderer::Gpu => painter.as_ref(),
+            _ => &MeshPainter,
         };
-        ui.painter().add(callback);
+        painter.paint(ui.painter(), rect, &gtype, hue, self.render_options);
         response
     }
 
+    // Gradients compiled on first use can still fail, which switches to
+    // meshes just like a failure at startup.
+    fn poll_render_error(&mut self) {
+        if let Some(error) = self.gpu_painter.as_ref().and_then(|p| p.take_error()) {
+            self.render_error = Some(error);
+            self.render_error_seen = false;
+            self.renderer = Renderer::Mesh;
+        }
+    }
+
+    fn draw_render_error(&self, ui: &mut egui::Ui, rect: Rect) {
+        if self.render_error.is_none() || self.render_error_seen {
+            return;
+        }
+        let galley = ui.painter().layout(
+            String::from("Shaders failed, using mesh renderer (right-click for details)"),
+            egui::FontId::proportional(11.0),
+            ui.visuals().error_fg_color,
+            rect.width() - 8.0,
+        );
+        let banner = Rect::from_min_size(rect.min, Vec2::new(rect.width(), galley.size().y + 6.0));
+        ui.painter()
+            .rect_filled(banner, 0.0, Color32::from_black_alpha(180));
+        ui.painter().galley(
+            banner.min + Vec2::new(4.0, 3.0),
+            galley,
+            Color32::PLACEHOLDER,
+        );
+    }
+
+    fn draw_gradient_menu(&mut self, ui: &mut egui::Ui) {
+        if let Some(error) = &self.render_error {
+            self.render_error_seen = true;
+            ui.set_max_width(300.0);
+            ui.colored_label(ui.visuals().error_fg_color, error);
+            ui.separator();
+        }
+        ui.label("Shape");
+        for shape in Shape::ALL {
+            ui.radio_value(&mut self.shape, shape, shape.name());
+        }
+        if let Shape::Plane(plane) = &mut self.shape {
+            ui.separator();
+            draw_plane_menu(ui, plane);
+        } else if ui.button("Custom plane").clicked() {
+            self.shape = Shape::Plane(Plane {
+                strip: true,
+                ..Plane::SV
+            });
+        }
+        ui.separator();
+        ui.label("Renderer");
+        if let Some(painter) = &self.gpu_painter {
+            let label = format!("{} shaders", painter.name());
+            ui.radio_value(&mut self.renderer, Renderer::Gpu, label);
+        }
+        ui.radio_value(&mut self.renderer, Renderer::Mesh, MeshPainter.name());
+        ui.separator();
+        // Only the shaders can dither or draw exact steps.
+        let shaded = self.renderer == Renderer::Gpu && self.gpu_painter.is_some();
+        ui.add_enabled_ui(shaded, |ui| {
+            for dither in Dither::ALL {
+                ui.radio_value(&mut self.render_options.dither, dither, dither.name());
+            }
+            ui.checkbox(&mut self.render_options.quantize, "Show quantized steps")
+                .on_hover_text("Draw only the values the picker can produce");
+        });
+    }
+
     fn handle_gradient_click(&mut self, response: &Response, gtype: &GradientType) {
         let click = match gtype {
-            GradientType::Gradient => &mut self.gradient_click,
+            GradientType::Gradient(_) => &mut self.gradient_click,
             GradientType::Slider(stype) => self.slider_clicks.get_mut(stype).unwrap(),
         };
-        if response.contains_pointer() && response.is_pointer_button_down_on() && !*click {
+        let primary_down = response.ctx.input(|i| i.pointer.primary_down());
+        if response.contains_pointer()
+            && response.is_pointer_button_down_on()
+            && primary_down
+            && !*click
+        {
             *click = true;
+            self.drag_position = None;
         } else if !response.is_pointer_button_down_on() && *click {
             *click = false;
+            self.drag_position = None;
         }
         if !*click {
             return;
@@ -301,21 +672,36 @@ impl App {
 
         let rect = response.interact_rect;
         if let Some(pos) = response.hover_pos() {
-            let pos = Vec2::new(
-                pos.x.clamp(rect.min.x, rect.max.x),
-                pos.y.clamp(rect.min.y, rect.max.y),
-            );
-            self.set_color(match gtype {
-                GradientType::Gradient => {
-                    let s = (pos.x - rect.min.x) / rect.width() * 100.0;
-                    let v = (1.0 - (pos.y - rect.min.y) / rect.height()) * 100.0;
-                    Color::from_hsv(self.color.h, s as u16, v as u16)
+            let modifiers = response.ctx.input(|i| i.modifiers);
+            // Shift trades pointer position for scaled-down pointer motion.
+            let pos = match self.drag_position {
+                Some(last) if modifiers.shift => {
+                    last + response.drag_delta() * self.fine_drag_sensitivity
+                }
+                _ => pos,
+            };
+            let pos = pos.clamp(rect.min, rect.max);
+            if self.drag_position.is_none() {
+                self.drag_part = self.shape.part_at(rect, pos);
+            }
+            self.drag_position = Some(pos);
+            let snap = |label: &str, value: f32| {
+                if modifiers.ctrl {
+                    self.snap_value(label, value)
+                } else {
+                    value
+                }
+            };
+            match gtype {
+                GradientType::Gradient(shape) => {
+                    self.set_color(shape.pick(self.drag_part, rect, pos, &self.color, snap))
                 }
                 GradientType::Slider(stype) => {
                     let t = (pos.x - rect.min.x) / (rect.max.x - rect.min.x);
-                    self.change_color_value(stype.clone(), t, true)
+                    let value = snap(stype, t * max_by_name(stype) as f32);
+                    self.set_color_value(stype.clone(), value, false);
                 }
-            });
+            }
         }
     }
 
@@ -325,20 +711,131 @@ impl App {
         response: &Response,
         gtype: &GradientType,
     ) {
-        let scroll_detla = ui.input(|i| i.raw_scroll_delta);
-        if scroll_detla.y == 0.0 || !response.contains_pointer() {
+        // Shift turns vertical scrolling into horizontal, so sum both axes.
+        let (scroll_delta, modifiers) =
+            ui.input(|i| (i.raw_scroll_delta.x + i.raw_scroll_delta.y, i.modifiers));
+        if scroll_delta == 0.0 || !response.contains_pointer() {
             return;
         }
+        // Several notches in one frame means the wheel is spinning fast,
+        // so accelerate rather than step linearly.
+        let line = ui.ctx().options(|o| o.line_scroll_speed);
+        let notches = (scroll_delta.abs() / line).ceil() as i32;
+        let steps = scroll_delta.signum() as i32 * notches * notches;
         match gtype {
-            GradientType::Gradient => (),
+            // Planes scroll through the channel that isn't on an axis.
+            GradientType::Gradient(Shape::Plane(plane)) => {
+                let channel = plane.z();
+                let label = plane.model.channels()[channel];
+                let value = self.plane_value(plane, channel);
+                let value = self.scroll_value(label, value, steps, modifiers);
+                let mut coords = plane.model.coords(&self.color);
+                coords[channel] = plane_coord(plane, channel, value);
+                self.set_color(plane.model.color(coords));
+            }
+            GradientType::Gradient(_) => {
+                let h = self
+                    .scroll_value("h", self.color.h as i32, steps, modifiers)
+                    .rem_euclid(360);
+                self.set_color(Color::from_hsv(h as u16, self.color.s, self.color.v));
+            }
             GradientType::Slider(stype) => {
-                let value = self.color.value_by_name(stype) as i32
-                    + if scroll_detla.y > 0.0 { 1 } else { -1 };
-                self.set_color(self.change_color_value(stype.clone(), value as f32, false));
+                let value = self.model_value(stype) as i32;
+                let value = self.scroll_value(stype, value, steps, modifiers);
+                self.set_color_value(stype.clone(), value as f32, false);
             }
         };
     }
 
+    // Ctrl jumps between snap increments, Shift moves ten units per step.
+    fn scroll_value(&self, label: &str, value: i32, steps: i32, modifiers: Modifiers) -> i32 {
+        if modifiers.ctrl {
+            let increment = self.snap_increments[label] as i32;
+            let base = if steps > 0 {
+                value.div_euclid(increment)
+            } else {
+                (value + increment - 1).div_euclid(increment)
+            };
+            (base + steps) * increment
+        } else if modifiers.shift {
+            value + steps * 10
+        } else {
+            value + steps
+        }
+    }
+
+    fn snap_value(&self, label: &str, value: f32) -> f32 {
+        let increment = self.snap_increments[label] as f32;
+        (value / increment).round() * increment
+    }
+
+    fn handle_gradient_keys(
+        &mut self,
+        ui: &mut egui::Ui,
+        response: &Response,
+        gtype: &GradientType,
+    ) {
+        if response.clicked() || response.drag_started() {
+            response.request_focus();
+        }
+        if !response.has_focus() {
+            return;
+        }
+        ui.memory_mut(|m| {
+            m.set_focus_lock_filter(
+                response.id,
+                EventFilter {
+                    horizontal_arrows: true,
+                    vertical_arrows: true,
+                    ..Default::default()
+                },
+            )
+        });
+        let (dx, dy, modifiers) = ui.input(|i| {
+            let axis = |neg, pos| i.num_presses(pos) as i32 - i.num_presses(neg) as i32;
+            (
+                axis(Key::ArrowLeft, Key::ArrowRight),
+                axis(Key::ArrowDown, Key::ArrowUp),
+                i.modifiers,
+            )
+        });
+        if dx == 0 && dy == 0 {
+            return;
+        }
+        match gtype {
+            GradientType::Gradient(Shape::Plane(plane)) => {
+                let maxes = plane.model.maxes();
+                let mut coords = plane.model.coords(&self.color);
+                for (channel, delta) in [(plane.x, dx), (plane.y, dy)] {
+                    let value = self.plane_value(plane, channel)
+                        + delta * key_step(maxes[channel], modifiers);
+                    coords[channel] = plane_coord(plane, channel, value);
+                }
+                self.set_color(plane.model.color(coords));
+            }
+            GradientType::Gradient(_) => {
+                let s = self.color.s as i32 + dx * key_step(100, modifiers);
+                let v = self.color.v as i32 + dy * key_step(100, modifiers);
+                self.set_color(Color::from_hsv(
+                    self.color.h,
+                    s.max(0) as u16,
+                    v.max(0) as u16,
+                ));
+            }
+            GradientType::Slider(stype) => {
+                let step = key_step(max_by_name(stype), modifiers);
+                let value = self.model_value(stype) as i32 + (dx + dy) * step;
+                self.set_color_value(stype.clone(), value as f32, false);
+            }
+        }
+    }
+
+    // A plane channel of the current color in the units it's shown in.
+    fn plane_value(&self, plane: &Plane, channel: usize) -> i32 {
+        let max = plane.model.maxes()[channel] as f32;
+        (plane.model.coords(&self.color)[channel] * max).round() as i32
+    }
+
     fn change_color_value(&self, label: String, t: f32, scaled: bool) -> Color {
         match label.as_str() {
             "r" => Color::from_rgb(
@@ -371,10 +868,53 @@ impl App {
                 self.color.s,
                 self.get_fixed_color_value(t, 100, scaled),
             ),
+            "sl" | "l" => {
+                let [s, l] = ["sl", "l"].map(|n| self.replaced_value(n, &label, t, scaled));
+                Color::from_hsl(self.color.h, s, l)
+            }
+            "c" | "m" | "y" | "k" => {
+                let [c, m, y, k] =
+                    ["c", "m", "y", "k"].map(|n| self.replaced_value(n, &label, t, scaled));
+                Color::from_cmyk(c, m, y, k)
+            }
             _ => Color::from_rgb(255, 0, 0),
         }
     }
 
+    // The new value for the channel being changed, the current one for
+    // the others in the same model.
+    fn replaced_value(&self, name: &str, label: &str, t: f32, scaled: bool) -> u16 {
+        if name == label {
+            self.get_fixed_color_value(t, max_by_name(name), scaled)
+        } else {
+            self.model_value(name)
+        }
+    }
+
+    fn set_color_value(&mut self, label: String, t: f32, scaled: bool) {
+        let color = self.change_color_value(label.clone(), t, scaled);
+        let names: &[&str] = match label.as_str() {
+            "sl" | "l" => &["sl", "l"],
+            "c" | "m" | "y" | "k" => &["c", "m", "y", "k"],
+            _ => &[],
+        };
+        self.model_values = names
+            .iter()
+            .map(|n| (n.to_string(), self.replaced_value(n, &label, t, scaled)))
+            .collect();
+        self.model_values_hex.clone_from(&color.hex);
+        self.set_color(color);
+    }
+
+    // A channel as the sliders show it, preferring the HSL or CMYK values
+    // that made the current color.
+    fn model_value(&self, name: &str) -> u16 {
+        match self.model_values.get(name) {
+            Some(value) if self.model_values_hex == self.color.hex => *value,
+            _ => self.color.value_by_name(name),
+        }
+    }
+
     fn get_fixed_color_value(&self, t: f32, max: u16, scaled: bool) -> u16 {
         let mut value = t as u16;
         if scaled {
@@ -383,6 +923,16 @@ impl App {
         value.clamp(0, max)
     }
 
+    fn draw_focus_outline(&self, ui: &mut egui::Ui, response: &Response) {
+        if response.has_focus() {
+            ui.painter().rect_stroke(
+                response.rect.expand(3.0),
+                2.0,
+                Stroke::new(1.0, self.theme.fg_selected),
+            );
+        }
+    }
+
     fn draw_gradient_handle(
         &mut self,
         ui: &mut egui::Ui,
@@ -450,17 +1000,17 @@ impl App {
                         ));
                         ui.end_row();
 
-                        ui.label("CYMK:");
+                        ui.label("CMYK:");
                         let (c, y, m, k) = rgb_to_cymk(self.color.r, self.color.g, self.color.b);
                         ui.text_edit_singleline(&mut format!(
                             "{:.0}, {:.0}, {:.0}, {:.0}",
-                            c, y, m, k,
+                            c, m, y, k,
                         ));
                         ui.text_edit_singleline(&mut format!(
                             "{:.2}, {:.2}, {:.2}, {:.2}",
                             c as f32 * 0.01,
-                            y as f32 * 0.01,
                             m as f32 * 0.01,
+                            y as f32 * 0.01,
                             k as f32 * 0.01
                         ));
                         ui.end_row();
@@ -473,25 +1023,105 @@ impl App {
         ui.spacing_mut().button_padding = Vec2::new(8.0, 8.0);
         let picker_button =
             ImageButton::new(Image::new(egui::include_image!("../picker_icon.png")))
-                .tint(theme::THEME.fg)
+                .tint(self.theme.fg)
                 .rounding(4.0);
-        if ui.add_sized([30.0, 30.0], picker_button).clicked() {
-            self.run_hyprpicker();
+        let response = ui.add_sized([30.0, 30.0], picker_button);
+        let response = match &self.picker_error {
+            Some(error) => {
+                ui.painter().rect_stroke(
+                    response.rect,
+                    4.0,
+                    Stroke::new(1.0, ui.visuals().error_fg_color),
+                );
+                response.on_hover_text(error)
+            }
+            None => response,
+        };
+        if response.clicked() {
+            self.picker_error = self.run_hyprpicker().err();
         }
-        let (rect, _) =
-            ui.allocate_exact_size(Vec2::new(100.0, 32.0), Sense::focusable_noninteractive());
-        ui.painter().rect_filled(rect, 2.0, self.color.to_color32());
-        ui.painter().rect_stroke(
-            rect,
-            1.0,
-            Stroke {
-                width: 1.0,
-                color: theme::THEME.bg_selected,
-            },
+        let loupe_button = Button::new("\u{1F50D}").rounding(4.0);
+        let response = ui
+            .add_enabled_ui(self.capture.is_none(), |ui| {
+                ui.add_sized([30.0, 30.0], loupe_button)
+            })
+            .inner;
+        let response = match &self.capture_error {
+            Some(error) => {
+                ui.painter().rect_stroke(
+                    response.rect,
+                    4.0,
+                    Stroke::new(1.0, ui.visuals().error_fg_color),
+                );
+                response.on_hover_text(error)
+            }
+            None => response.on_hover_text(
+                "Pick from a magnified screenshot, drag to sample an area\n\
+                 Right-click for sampling options",
+            ),
+        };
+        response.context_menu(|ui| self.draw_sampling_menu(ui));
+        if response.clicked() {
+            self.start_capture(ui.ctx());
+        }
+        let image_button = Button::new("\u{1F5BC}")
+            .rounding(4.0)
+            .selected(self.image_open);
+        let response = ui
+            .add_sized([30.0, 30.0], image_button)
+            .on_hover_text("Pick from an image file, or drop one on the window");
+        response.context_menu(|ui| self.draw_sampling_menu(ui));
+        if response.clicked() {
+            self.image_open = !self.image_open;
+            if self.image_open && self.image_view.is_none() {
+                self.start_image_load(ui.ctx(), None);
+            }
+        }
+        let palette_button = Button::new("\u{1F3A8}")
+            .rounding(4.0)
+            .selected(self.palette_open);
+        if ui
+            .add_sized([30.0, 30.0], palette_button)
+            .on_hover_text("Extract a palette from the loaded image")
+            .clicked()
+        {
+            self.palette_open = !self.palette_open;
+        }
+        let templates_button = Button::new("\u{1F4C4}")
+            .rounding(4.0)
+            .selected(self.templates_open);
+        if ui
+            .add_sized([30.0, 30.0], templates_button)
+            .on_hover_text("Live theming: render templates and preview Hyprland colors")
+            .clicked()
+        {
+            self.templates_open = !self.templates_open;
+        }
+        self.draw_color_field(ui);
+    }
+
+    // The swatch fills whatever the hex field leaves. Service failures
+    // outline it and show on hover.
+    fn draw_color_field(&mut self, ui: &mut egui::Ui) {
+        let hex_width = 80.0;
+        let swatch_width = ui.available_width() - hex_width - ui.spacing().item_spacing.x;
+        let (rect, response) = ui.allocate_exact_size(
+            Vec2::new(swatch_width.max(100.0), 32.0),
+            Sense::focusable_noninteractive(),
         );
+        ui.painter().rect_filled(rect, 2.0, self.color.to_color32());
+        let stroke = match self.service_errors.is_empty() {
+            true => self.theme.bg_selected,
+            false => ui.visuals().error_fg_color,
+        };
+        ui.painter()
+            .rect_stroke(rect, 1.0, Stroke::new(1.0, stroke));
+        if !self.service_errors.is_empty() {
+            response.on_hover_text(self.service_errors.join("\n"));
+        }
         if ui
             .add_sized(
-                [80.0, 20.0],
+                [hex_width, 20.0],
                 TextEdit::singleline(&mut self.hex).vertical_align(Align::Center),
             )
             .changed()
@@ -511,34 +1141,1245 @@ impl App {
     fn set_color(&mut self, color: Color) {
         self.color = color;
         self.hex.clone_from(&self.color.hex);
-        let slider_labels = ["r", "g", "b", "h", "s", "v"];
-        for label in slider_labels.iter() {
+        self.slider_errors.clear();
+        for label in SLIDER_LABELS.iter() {
+            let value = self.model_value(label).to_string();
             if let Some(text) = self.slider_texts.get_mut(label.to_owned()) {
-                *text = self.color.value_by_name(label).to_string();
+                *text = value;
             }
         }
     }
 
+    fn report_service_error(&mut self, error: String) {
+        if !self.service_errors.contains(&error) {
+            self.service_errors.push(error);
+        }
+    }
+
+    // Colors taken from the screen or an image also go into the history.
+    fn set_picked_color(&mut self, color: Color) {
+        self.history.retain(|c| c.hex != color.hex);
+        self.history.insert(0, color.clone());
+        self.history.truncate(HISTORY_LEN);
+        self.set_color(color);
+    }
+
+    // Through wl-copy when it's around, since a hidden window can't set the
+    // Wayland clipboard.
+    fn copy_color(&self, ctx: &egui::Context) {
+        let copied = Command::new("wl-copy")
+            .arg(&self.color.hex)
+            .status()
+            .is_ok_and(|status| status.success());
+        if !copied {
+            ctx.copy_text(self.color.hex.clone());
+        }
+    }
+
+    // Plain integers apply while typing, anything else waits for Enter or
+    // focus loss so half-typed expressions aren't evaluated.
     fn on_slider_text_changed(&mut self, label: String) {
-        if let Some(text) = self.slider_texts.get_mut(&label) {
-            match text.parse::<f32>() {
+        self.slider_errors.remove(&label);
+        if let Some(text) = self.slider_texts.get(&label) {
+            // `parse` takes a leading '+', which has to wait for the commit
+            // to be read as relative.
+            let text = text.trim();
+            let plain = !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit());
+            if let Some(t) = plain.then(|| text.parse::<u16>().ok()).flatten() {
+                self.set_color_value(label, t as f32, false);
+            }
+        }
+    }
+
+    fn on_slider_text_committed(&mut self, label: String) {
+        if let Some(text) = self.slider_texts.get(&label) {
+            let current = self.model_value(&label) as f32;
+            let max = max_by_name(&label) as f32;
+            match expression::evaluate(text, current, max, label == "h") {
                 Ok(t) => {
-                    self.set_color(self.change_color_value(label, t, false));
+                    self.set_color_value(label, t.round(), false);
+                }
+                Err(error) => {
+                    self.slider_errors.insert(label, error);
+                }
+            }
+        }
+    }
+
+    // Wayland has no way to hide a window, so it's minimized instead, and
+    // showing it again relies on the compositor honoring the focus request.
+    // Hyprland ignores both, there it's moved off to a special workspace.
+    fn set_visible(&self, ctx: &egui::Context, visible: bool) -> Result<(), String> {
+        if let Some(hyprland) = self.hyprland.as_ref() {
+            let (action, result) = match visible {
+                true => ("show", hyprland.show_window()),
+                false => ("hide", hyprland.hide_window()),
+            };
+            return result.map_err(|err| format!("Failed to {action} the window: {err}"));
+        }
+        ctx.send_viewport_cmd(ViewportCommand::Visible(visible));
+        ctx.send_viewport_cmd(ViewportCommand::Minimized(!visible));
+        if visible {
+            ctx.send_viewport_cmd(ViewportCommand::Focus);
+        }
+        Ok(())
+    }
+
+    // For hiding around a pick, where nobody waits for a reply.
+    fn set_visible_or_report(&mut self, ctx: &egui::Context, visible: bool) {
+        if let Err(error) = self.set_visible(ctx, visible) {
+            self.hyprland_error = Some(error);
+        }
+    }
+
+    // Screenshots can take a while through the portal, so they're taken on
+    // a thread and picked up in `poll_capture`.
+    fn start_capture(&mut self, ctx: &egui::Context) {
+        // Otherwise the window covers what it's meant to pick from.
+        let hide = !self.hidden;
+        if hide {
+            self.set_visible_or_report(ctx, false);
+        }
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            if hide {
+                std::thread::sleep(HIDE_DELAY);
+            }
+            let _ = sender.send(capture::screenshot());
+            ctx.request_repaint();
+        });
+        self.capture = Some(receiver);
+        self.capture_error = None;
+    }
+
+    fn poll_capture(&mut self, ctx: &egui::Context) {
+        let Some(result) = self.capture.as_ref().and_then(|r| r.try_recv().ok()) else {
+            return;
+        };
+        self.capture = None;
+        match result {
+            Ok(image) => {
+                self.loupe = Some(Loupe::new(ctx, image, self.sampling));
+                self.set_visible_or_report(ctx, true);
+                ctx.send_viewport_cmd(ViewportCommand::Fullscreen(true));
+            }
+            Err(error) => {
+                if !self.hidden {
+                    self.set_visible_or_report(ctx, true);
+                }
+                for request in self.pick_requests.drain(..) {
+                    request.reply(Err(error.clone()));
+                }
+                self.capture_error = Some(error);
+            }
+        }
+    }
+
+    fn draw_loupe(&mut self, ctx: &egui::Context) {
+        let event = egui::CentralPanel::default()
+            .frame(Frame::none())
+            .show(ctx, |ui| {
+                self.loupe.as_mut().and_then(|loupe| loupe.show(ui))
+            })
+            .inner;
+        if let Some(event) = event {
+            let reply = match event {
+                LoupeEvent::Pick(color) => {
+                    self.set_picked_color(color);
+                    Ok(self.color.hex.clone())
+                }
+                LoupeEvent::Cancel => Err(String::from(ipc::CANCELLED)),
+            };
+            for request in self.pick_requests.drain(..) {
+                request.reply(reply.clone());
+            }
+            if let Some(loupe) = self.loupe.take() {
+                self.sampling = loupe.sampling;
+            }
+            ctx.send_viewport_cmd(ViewportCommand::Fullscreen(false));
+            if self.hidden {
+                self.set_visible_or_report(ctx, false);
+            }
+        }
+    }
+
+    // A small undecorated window on top, for binding `waycolor compact` to a
+    // hotkey: the SV plane, hue slider, swatch and hex field. It's a second
+    // viewport, so the main window can stay hidden. Escape or focus loss
+    // closes it.
+    fn draw_compact(&mut self, ctx: &egui::Context) {
+        let builder = ViewportBuilder::default()
+            .with_title(COMPACT_TITLE)
+            .with_app_id("WayColor")
+            .with_decorations(false)
+            .with_always_on_top()
+            .with_resizable(false)
+            .with_inner_size(COMPACT_SIZE);
+        let id = ViewportId::from_hash_of("compact");
+        let close = ctx.show_viewport_immediate(id, builder, |ctx, _| {
+            let escape = ctx.input(|i| i.key_pressed(Key::Escape));
+            let (focused, close) =
+                ctx.input(|i| (i.viewport().focused, i.viewport().close_requested()));
+            let had_focus = self.compact.as_ref().is_some_and(|c| c.focused);
+            if focused == Some(true) && !had_focus {
+                self.compact = Some(Compact { focused: true });
+                self.place_compact();
+            }
+            egui::CentralPanel::default().show(ctx, |ui| self.draw_compact_controls(ui));
+            escape || close || (had_focus && focused == Some(false))
+        });
+        if close {
+            self.compact = None;
+        }
+    }
+
+    // Always the SV plane, a shape picked from its menu here is dropped.
+    fn draw_compact_controls(&mut self, ui: &mut egui::Ui) {
+        ui.spacing_mut().item_spacing = Vec2::new(self.spacing, self.spacing);
+        let margin = self.gradient_margin * 2.0;
+        let available = ui.available_size();
+        let width = available.x - margin;
+        let controls = self.slider_height + margin * 2.0 + 32.0 + self.spacing * 2.0;
+        let plane = snap_to_pixels(ui, Vec2::new(width, available.y - controls).max(Vec2::ZERO));
+        let slider = snap_to_pixels(ui, Vec2::new(width, self.slider_height));
+        let color = self.color.clone();
+        let shape = std::mem::replace(&mut self.shape, Shape::Plane(Plane::SV));
+        self.draw_gradient(ui, GradientType::Gradient(self.shape), plane, &color);
+        self.shape = shape;
+        self.draw_gradient(ui, GradientType::Slider(String::from("h")), slider, &color);
+        ui.horizontal(|ui| self.draw_color_field(ui));
+    }
+
+    // Hyprland tiles new windows, which the popup shouldn't be.
+    fn place_compact(&mut self) {
+        let Some(hyprland) = self.hyprland.as_ref() else {
+            return;
+        };
+        let window = format!("title:^({COMPACT_TITLE})$");
+        if let Err(error) = hyprland.float_at_cursor(&window, COMPACT_SIZE.into()) {
+            self.hyprland_error = Some(error);
+        }
+    }
+
+    // Commands from `waycolor msg` and later launches, see `ipc`. Picks are
+    // answered once the loupe is done.
+    fn poll_requests(&mut self, ctx: &egui::Context) {
+        while let Ok(request) = self.requests.try_recv() {
+            match &request.command {
+                ipc::Command::SetColor(color) => {
+                    self.set_color(color.clone());
+                    request.reply(Ok(self.color.hex.clone()));
+                }
+                ipc::Command::GetColor => request.reply(Ok(self.color.hex.clone())),
+                ipc::Command::Pick => {
+                    if self.capture.is_none() && self.loupe.is_none() {
+                        self.start_capture(ctx);
+                    }
+                    self.pick_requests.push(request);
+                }
+                ipc::Command::Show | ipc::Command::Hide | ipc::Command::Toggle => {
+                    self.hidden = match request.command {
+                        ipc::Command::Toggle => !self.hidden,
+                        _ => matches!(request.command, ipc::Command::Hide),
+                    };
+                    request.reply(self.set_visible(ctx, !self.hidden).map(|_| String::new()));
+                }
+                ipc::Command::Compact => {
+                    self.compact = match self.compact {
+                        Some(_) => None,
+                        None => Some(Compact::default()),
+                    };
+                    ctx.request_repaint();
+                    request.reply(Ok(String::new()));
+                }
+                ipc::Command::Copy => {
+                    self.copy_color(ctx);
+                    request.reply(Ok(self.color.hex.clone()));
+                }
+                ipc::Command::Enable(service) => {
+                    request.reply(self.enable(ctx, *service).map(|_| String::new()));
+                }
+                ipc::Command::Quit => {
+                    ctx.send_viewport_cmd(ViewportCommand::Close);
+                    request.reply(Ok(String::new()));
+                }
+            }
+        }
+    }
+
+    // Enabling a service that's already running does nothing.
+    fn enable(&mut self, ctx: &egui::Context, service: Service) -> Result<(), String> {
+        let (ctx, sender) = (ctx.clone(), self.sender.clone());
+        let result = match service {
+            Service::PortalBackend if self.portal_backend.is_none() => {
+                portal_backend::Backend::start(ctx, sender)
+                    .map(|backend| self.portal_backend = Some(backend))
+                    .map_err(|err| format!("Failed to register {}: {err}", portal_backend::NAME))
+            }
+            Service::Dbus if self.dbus.is_none() => dbus::Service::start(ctx, sender)
+                .map(|dbus| self.dbus = Some(dbus))
+                .map_err(|err| format!("Failed to register {}: {err}", dbus::NAME)),
+            Service::Tray if self.tray.is_none() => Tray::start(ctx, sender)
+                .map(|tray| self.tray = Some(tray))
+                .map_err(|err| format!("Failed to create tray item: {err}")),
+            _ => Ok(()),
+        };
+        if let Err(error) = &result {
+            self.report_service_error(error.clone());
+        }
+        result
+    }
+
+    // Without a path this asks the file chooser portal first. Both the
+    // dialog and decoding can take a while, hence the thread.
+    fn start_image_load(&mut self, ctx: &egui::Context, path: Option<PathBuf>) {
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let result = match path {
+                Some(path) => Ok(Some(path)),
+                None => portal::choose_image(),
+            }
+            .and_then(|path| {
+                path.map(|path| {
+                    let name = path
+                        .file_name()
+                        .map_or(String::new(), |n| n.to_string_lossy().to_string());
+                    capture::load_image(&path).map(|image| (name, image))
+                })
+                .transpose()
+            });
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+        self.image_load = Some(receiver);
+        self.image_error = None;
+    }
+
+    fn poll_image_load(&mut self, ctx: &egui::Context) {
+        let Some(result) = self.image_load.as_ref().and_then(|r| r.try_recv().ok()) else {
+            return;
+        };
+        self.image_load = None;
+        match result {
+            Ok(Some((name, image))) => {
+                self.image_view = Some(ImageView::new(ctx, name, image));
+                self.image_open = true;
+            }
+            Ok(None) => {}
+            Err(error) => {
+                self.image_error = Some(error);
+                self.image_open = true;
+            }
+        }
+    }
+
+    fn draw_image_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.image_open;
+        let title = self
+            .image_view
+            .as_ref()
+            .map_or("Image", |view| view.name.as_str())
+            .to_string();
+        egui::Window::new(title)
+            .id(egui::Id::new("image_window"))
+            .open(&mut open)
+            .default_size([360.0, 300.0])
+            .constrain(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let loading = self.image_load.is_some();
+                    if ui
+                        .add_enabled(!loading, Button::new("Open\u{2026}"))
+                        .clicked()
+                    {
+                        self.start_image_load(ui.ctx(), None);
+                    }
+                    let status = match self.image_view.as_ref().and_then(|v| v.hovered()) {
+                        Some(([x, y], sample)) => format!(
+                            "{}  ({x}, {y})  \u{b1}{:.1}",
+                            sample.color.hex, sample.spread
+                        ),
+                        None if loading => String::from("Loading\u{2026}"),
+                        None => String::new(),
+                    };
+                    ui.label(status);
+                });
+                if let Some(error) = &self.image_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                let picked = match self.image_view.as_mut() {
+                    Some(view) => view.show(ui, &self.sampling),
+                    None => {
+                        ui.centered_and_justified(|ui| {
+                            ui.weak("Drop a PNG or JPEG here");
+                        });
+                        None
+                    }
+                };
+                if let Some(color) = picked {
+                    self.set_picked_color(color);
+                }
+            });
+        self.image_open = open;
+    }
+
+    fn draw_palette_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.palette_open;
+        egui::Window::new("Palette")
+            .open(&mut open)
+            .default_width(300.0)
+            .constrain(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("palette_method")
+                        .selected_text(self.palette_method.name())
+                        .show_ui(ui, |ui| {
+                            for method in Method::ALL {
+                                ui.selectable_value(
+                                    &mut self.palette_method,
+                                    method,
+                                    method.name(),
+                                );
+                            }
+                        });
+                    ui.add(
+                        egui::DragValue::new(&mut self.palette_count)
+                            .range(2..=16)
+                            .suffix(" colors"),
+                    );
+                    let extracting = self.palette_extract.is_some();
+                    let extract = ui
+                        .add_enabled(
+                            self.image_view.is_some() && !extracting,
+                            Button::new("Extract"),
+                        )
+                        .on_disabled_hover_text(match extracting {
+                            true => "Extracting…",
+                            false => "Load an image first",
+                        });
+                    if extract.clicked() {
+                        self.start_palette_extract(ui.ctx());
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let loading = self.wallpaper_load.is_some();
+                    let button = ui
+                        .add_enabled(!loading, Button::new("From wallpaper"))
+                        .on_hover_text(
+                            "Extract a palette and terminal scheme from the current \
+                             wallpaper, or from the given path",
+                        );
+                    if button.clicked() {
+                        self.start_wallpaper_load(ui.ctx());
+                    }
+                    ui.add(
+                        TextEdit::singleline(&mut self.wallpaper_path)
+                            .hint_text("Detect from swww, hyprpaper, swaybg"),
+                    );
+                });
+                ui.add_space(6.0);
+                ui.horizontal_wrapped(|ui| {
+                    match draw_swatches(ui, &self.palette) {
+                        Some(SwatchEvent::Pick(color)) => self.set_color(color),
+                        Some(SwatchEvent::Remove(i)) => {
+                            self.palette.remove(i);
+                            self.scheme_background = None;
+                        }
+                        None => {}
+                    }
+                    let add = ui
+                        .add_sized([32.0, 32.0], Button::new("+"))
+                        .on_hover_text(format!("Add {}", self.color.hex));
+                    if add.clicked() {
+                        self.palette.push(Swatch {
+                            color: self.color.clone(),
+                            share: 0.0,
+                        });
+                    }
+                });
+                ui.add_space(6.0);
+                ui.add_enabled_ui(!self.palette.is_empty(), |ui| {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("palette_format")
+                            .selected_text(self.palette_format.name())
+                            .show_ui(ui, |ui| {
+                                for format in Format::ALL {
+                                    ui.selectable_value(
+                                        &mut self.palette_format,
+                                        format,
+                                        format.name(),
+                                    );
+                                }
+                            });
+                        if ui.button("Copy").clicked() {
+                            ui.ctx().copy_text(self.palette_text());
+                        }
+                        let saving = self.file_save.is_some();
+                        if ui
+                            .add_enabled(!saving, Button::new("Save\u{2026}"))
+                            .clicked()
+                        {
+                            let name = format!(
+                                "{}.{}",
+                                self.palette_name(),
+                                self.palette_format.extension()
+                            );
+                            self.start_save(ui.ctx(), "Save palette", name, self.palette_text());
+                        }
+                    });
+                });
+                self.draw_file_status(ui);
+                if let Some(error) = &self.wallpaper_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                ui.separator();
+                self.draw_scheme(ui);
+            });
+        self.palette_open = open;
+    }
+
+    fn draw_scheme(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        ui.add_enabled_ui(!self.palette.is_empty(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Terminal scheme");
+                // A chosen background decides this by itself.
+                ui.add_enabled_ui(self.scheme_background.is_none(), |ui| {
+                    changed |= ui
+                        .radio_value(&mut self.scheme_dark, true, "Dark")
+                        .changed();
+                    changed |= ui
+                        .radio_value(&mut self.scheme_dark, false, "Light")
+                        .changed();
+                });
+                let selected = match self.scheme_background {
+                    Some(i) => self.palette[i].color.hex.clone(),
+                    None => String::from("Auto"),
+                };
+                egui::ComboBox::from_id_source("scheme_background")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        changed |= ui
+                            .selectable_value(&mut self.scheme_background, None, "Auto")
+                            .changed();
+                        for (i, swatch) in self.palette.iter().enumerate() {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.scheme_background,
+                                    Some(i),
+                                    &swatch.color.hex,
+                                )
+                                .changed();
+                        }
+                    })
+                    .response
+                    .on_hover_text("Background");
+                if ui.button("Propose").clicked() {
+                    changed = true;
+                    self.scheme_open = true;
                 }
-                Err(_) => {
-                    self.set_color(self.color.clone());
+            });
+        });
+        if changed {
+            self.update_scheme();
+        }
+    }
+
+    fn draw_scheme_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.scheme_open && self.scheme.is_some();
+        egui::Window::new("Terminal scheme")
+            .open(&mut open)
+            .resizable(false)
+            .constrain(true)
+            .show(ctx, |ui| {
+                let Some(scheme) = &self.scheme else {
+                    return;
+                };
+                if let Some(slot) = draw_scheme_preview(ui, scheme, self.scheme_slot) {
+                    self.scheme_slot = Some(slot);
+                    self.set_color(scheme.get(slot).clone());
                 }
+                self.draw_scheme_slot(ui);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("scheme_terminal")
+                        .selected_text(self.scheme_terminal.name())
+                        .show_ui(ui, |ui| {
+                            for terminal in Terminal::ALL {
+                                ui.selectable_value(
+                                    &mut self.scheme_terminal,
+                                    terminal,
+                                    terminal.name(),
+                                );
+                            }
+                        });
+                    if ui.button("Copy").clicked() {
+                        ui.ctx().copy_text(self.scheme_text());
+                    }
+                    let saving = self.file_save.is_some();
+                    if ui
+                        .add_enabled(!saving, Button::new("Save\u{2026}"))
+                        .clicked()
+                    {
+                        let name = self.scheme_terminal.file_name(&self.palette_name());
+                        self.start_save(ui.ctx(), "Save terminal scheme", name, self.scheme_text());
+                    }
+                });
+                self.draw_file_status(ui);
+            });
+        self.scheme_open = open;
+    }
+
+    // The selected slot, its contrast, and a way to replace it with the
+    // color being edited in the picker.
+    fn draw_scheme_slot(&mut self, ui: &mut egui::Ui) {
+        let Some(scheme) = self.scheme.as_mut() else {
+            return;
+        };
+        let min_contrast = scheme.min_contrast();
+        let text = format!("Lowest text contrast {min_contrast:.1}:1");
+        if min_contrast < scheme::MIN_CONTRAST {
+            ui.colored_label(ui.visuals().warn_fg_color, text);
+        } else {
+            ui.weak(text);
+        }
+        if !scheme.unmet.is_empty() {
+            let names: Vec<String> = scheme.unmet.iter().map(Slot::name).collect();
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!("No contrast high enough for {}", names.join(", ")),
+            );
+        }
+        let Some(slot) = self.scheme_slot else {
+            ui.weak("Click a color to edit it");
+            return;
+        };
+        ui.horizontal(|ui| {
+            let color = scheme.get(slot);
+            let contrast = color.contrast(scheme.get(slot.against()));
+            ui.label(format!(
+                "{}  {}  {contrast:.1}:1 against {}",
+                slot.name(),
+                color.hex,
+                slot.against().name().to_lowercase()
+            ));
+            let current = &self.color;
+            let button = ui
+                .add_enabled(current.hex != color.hex, Button::new("Use picked"))
+                .on_hover_text(format!("Replace with {}", current.hex));
+            if button.clicked() {
+                scheme.set(slot, current.clone());
             }
+        });
+    }
+
+    fn scheme_text(&self) -> String {
+        self.scheme.as_ref().map_or(String::new(), |scheme| {
+            self.scheme_terminal.export(&self.palette_name(), scheme)
+        })
+    }
+
+    fn draw_file_status(&self, ui: &mut egui::Ui) {
+        match &self.file_status {
+            Some(Ok(status)) => {
+                ui.weak(status);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            None => {}
+        }
+    }
+
+    fn set_palette(&mut self, swatches: Vec<Swatch>, with_scheme: bool) {
+        self.palette = swatches;
+        self.wallpaper_error = None;
+        self.scheme_background = None;
+        self.scheme_slot = None;
+        self.scheme = None;
+        if with_scheme {
+            self.update_scheme();
         }
     }
 
-    fn run_hyprpicker(&mut self) {
+    fn update_scheme(&mut self) {
+        let background = self
+            .scheme_background
+            .and_then(|i| self.palette.get(i))
+            .map(|swatch| &swatch.color);
+        self.scheme = (!self.palette.is_empty())
+            .then(|| Scheme::propose(&self.palette, self.scheme_dark, background));
+    }
+
+    // Median cut and k-means over a full-size image take long enough to
+    // stall a frame, so they run on a thread like loading does.
+    fn start_palette_extract(&mut self, ctx: &egui::Context) {
+        let Some(view) = &self.image_view else {
+            return;
+        };
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let image = view.image().clone();
+        let (count, method) = (self.palette_count, self.palette_method);
+        std::thread::spawn(move || {
+            let _ = sender.send(palette::extract(&image, count, method));
+            ctx.request_repaint();
+        });
+        self.palette_extract = Some(receiver);
+    }
+
+    fn poll_palette_extract(&mut self) {
+        let Some(swatches) = self
+            .palette_extract
+            .as_ref()
+            .and_then(|r| r.try_recv().ok())
+        else {
+            return;
+        };
+        self.palette_extract = None;
+        self.set_palette(swatches, self.scheme.is_some());
+    }
+
+    fn start_wallpaper_load(&mut self, ctx: &egui::Context) {
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let path = self.wallpaper_path.trim().to_string();
+        let (count, method) = (self.palette_count, self.palette_method);
+        std::thread::spawn(move || {
+            let result = match path.is_empty() {
+                true => wallpaper::locate(),
+                false => Ok(paths::expand_home(&path)),
+            }
+            .and_then(|path| {
+                let name = path
+                    .file_name()
+                    .map_or(String::new(), |n| n.to_string_lossy().to_string());
+                let image = capture::load_image(&path)?;
+                let swatches = palette::extract(&image, count, method);
+                Ok((name, image, swatches))
+            });
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+        self.wallpaper_load = Some(receiver);
+    }
+
+    fn poll_wallpaper_load(&mut self, ctx: &egui::Context) {
+        let Some(result) = self.wallpaper_load.as_ref().and_then(|r| r.try_recv().ok()) else {
+            return;
+        };
+        self.wallpaper_load = None;
+        match result {
+            Ok((name, image, swatches)) => {
+                self.image_view = Some(ImageView::new(ctx, name, image));
+                self.set_palette(swatches, true);
+                self.scheme_open = true;
+            }
+            Err(error) => {
+                self.wallpaper_error = Some(error);
+            }
+        }
+    }
+
+    fn palette_name(&self) -> String {
+        self.image_view
+            .as_ref()
+            .and_then(|view| view.name.rsplit_once('.').map(|(stem, _)| stem.to_string()))
+            .unwrap_or_else(|| String::from("WayColor"))
+    }
+
+    fn palette_text(&self) -> String {
+        self.palette_format
+            .export(&self.palette_name(), &self.palette)
+    }
+
+    fn start_save(&mut self, ctx: &egui::Context, title: &str, name: String, text: String) {
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let title = title.to_string();
+        std::thread::spawn(move || {
+            let result = portal::choose_save_path(&title, &name).and_then(|path| {
+                path.map(|path| {
+                    std::fs::write(&path, text)
+                        .map(|_| path)
+                        .map_err(|err| format!("Failed to save: {err}"))
+                })
+                .transpose()
+            });
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+        self.file_save = Some(receiver);
+        self.file_status = None;
+    }
+
+    fn poll_file_save(&mut self) {
+        let Some(result) = self.file_save.as_ref().and_then(|r| r.try_recv().ok()) else {
+            return;
+        };
+        self.file_save = None;
+        self.file_status = match result {
+            Ok(Some(path)) => Some(Ok(format!("Saved to {}", path.display()))),
+            Ok(None) => None,
+            Err(error) => Some(Err(error)),
+        };
+    }
+
+    fn draw_templates_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.templates_open;
+        egui::Window::new("Live theming")
+            .open(&mut open)
+            .default_width(520.0)
+            .constrain(true)
+            .show(ctx, |ui| {
+                if self.hyprland.is_some() {
+                    self.draw_hyprland_preview(ui);
+                    ui.separator();
+                }
+                ui.strong("Templates");
+                let mut removed = None;
+                Grid::new("templates")
+                    .num_columns(6)
+                    .spacing([6.0, 4.0])
+                    .show(ui, |ui| {
+                        for label in ["", "Name", "Template", "Target", "Reload command", ""] {
+                            ui.weak(label);
+                        }
+                        ui.end_row();
+                        for (i, template) in self.templates.entries.iter_mut().enumerate() {
+                            ui.checkbox(&mut template.enabled, "");
+                            let fields = [
+                                (&mut template.name, 70.0, ""),
+                                (&mut template.source, 120.0, "~/templates/kitty.conf"),
+                                (&mut template.target, 120.0, "~/.config/kitty/colors.conf"),
+                                (&mut template.reload, 110.0, "pkill -USR1 kitty"),
+                            ];
+                            for (text, width, hint) in fields {
+                                ui.add(
+                                    TextEdit::singleline(text)
+                                        .desired_width(width)
+                                        .hint_text(hint),
+                                );
+                            }
+                            if ui
+                                .small_button("\u{2715}")
+                                .on_hover_text("Remove")
+                                .clicked()
+                            {
+                                removed = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(i) = removed {
+                    self.templates.entries.remove(i);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Add").clicked() {
+                        let name = format!("template{}", self.templates.entries.len() + 1);
+                        self.templates.entries.push(Template {
+                            name,
+                            enabled: true,
+                            ..Default::default()
+                        });
+                    }
+                    if ui.button("Save").clicked() {
+                        self.templates_status = Some(
+                            self.templates
+                                .save()
+                                .map(|path| format!("Saved to {}", path.display())),
+                        );
+                    }
+                    let rendering = self.templates.is_rendering();
+                    if ui
+                        .add_enabled(!rendering, Button::new("Render now"))
+                        .clicked()
+                    {
+                        let values = Values::new(&self.color, &self.palette, self.scheme.as_ref());
+                        self.templates.render(ui.ctx(), values);
+                    }
+                });
+                match &self.templates_status {
+                    Some(Ok(status)) => {
+                        ui.weak(status);
+                    }
+                    Some(Err(error)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                    None => {}
+                }
+                for (name, result) in &self.templates.results {
+                    match result {
+                        Ok(()) => ui.weak(format!("{name}: rendered")),
+                        Err(error) => ui
+                            .colored_label(ui.visuals().error_fg_color, format!("{name}: {error}")),
+                    };
+                }
+                ui.collapsing("Placeholders", |ui| {
+                    ui.label(
+                        "{color} the picked color\n\
+                         {palette0}, {palette1}, \u{2026} palette swatches\n\
+                         {background}, {foreground}, {cursor}, {selection_background}, \
+                         {selection_foreground}, {color0} \u{2026} {color15} the terminal scheme\n\
+                         Suffixes: .hex (default), .strip, .rgb, .r, .g, .b, .h, .s, .v\n\
+                         Double braces for literal ones: {{ }}",
+                    );
+                });
+            });
+        self.templates_open = open;
+    }
+
+    fn draw_hyprland_preview(&mut self, ui: &mut egui::Ui) {
+        ui.strong("Hyprland");
+        ui.horizontal(|ui| {
+            let previewing = self.hyprland_preview.is_some();
+            ui.add_enabled(
+                !previewing,
+                TextEdit::singleline(&mut self.hyprland_keyword).desired_width(200.0),
+            );
+            if !previewing {
+                let button = ui
+                    .button("Preview")
+                    .on_hover_text("Apply the picked color to this option as it changes");
+                if button.clicked() {
+                    if let Some(hyprland) = self.hyprland.clone() {
+                        let keyword = self.hyprland_keyword.trim();
+                        match Preview::start(hyprland, keyword) {
+                            Ok(preview) => {
+                                self.hyprland_preview = Some(preview);
+                                self.hyprland_error = None;
+                            }
+                            Err(error) => self.hyprland_error = Some(error),
+                        }
+                    }
+                }
+            } else {
+                if ui.button("Keep").clicked() {
+                    self.hyprland_preview = None;
+                }
+                if ui
+                    .button("Cancel")
+                    .on_hover_text("Restore the original (Esc)")
+                    .clicked()
+                {
+                    self.cancel_hyprland_preview();
+                }
+            }
+        });
+        if let Some(error) = &self.hyprland_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+    }
+
+    fn cancel_hyprland_preview(&mut self) {
+        if let Some(preview) = self.hyprland_preview.take() {
+            if let Err(error) = preview.cancel() {
+                self.hyprland_error = Some(error);
+            }
+        }
+    }
+
+    fn draw_sampling_menu(&mut self, ui: &mut egui::Ui) {
+        ui.label("Sample area");
+        for size in Sampling::SIZES {
+            let label = format!("{size}\u{d7}{size}");
+            ui.radio_value(&mut self.sampling.size, size, label);
+        }
+        ui.separator();
+        for reduce in Reduce::ALL {
+            ui.radio_value(&mut self.sampling.reduce, reduce, reduce.name());
+        }
+    }
+
+    // An empty output means the pick was cancelled, which isn't an error.
+    fn run_hyprpicker(&mut self) -> Result<(), String> {
         let output = Command::new("/bin/hyprpicker")
             .output()
-            .expect("Failed to get 'hyprpicker' output.");
+            .map_err(|err| format!("Failed to run hyprpicker: {err}"))?;
+        if !output.status.success() {
+            return Err(format!(
+                "hyprpicker failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
         let hex = String::from_utf8_lossy(&output.stdout).trim().to_string();
         if let Some(hex) = Color::from_hex(hex) {
-            self.set_color(hex);
+            self.set_picked_color(hex);
+        }
+        Ok(())
+    }
+}
+
+enum SwatchEvent {
+    Pick(Color),
+    Remove(usize),
+}
+
+// Extracted swatches show how much of the image they cover, added ones
+// don't. Swatches are removed through their context menu.
+fn draw_swatches(ui: &mut egui::Ui, swatches: &[Swatch]) -> Option<SwatchEvent> {
+    let mut event = None;
+    ui.spacing_mut().item_spacing = Vec2::new(4.0, 4.0);
+    for (i, swatch) in swatches.iter().enumerate() {
+        ui.vertical(|ui| {
+            let (rect, response) = ui.allocate_exact_size(Vec2::splat(32.0), Sense::click());
+            ui.painter()
+                .rect_filled(rect, 2.0, swatch.color.to_color32());
+            if response.hovered() {
+                ui.painter()
+                    .rect_stroke(rect, 2.0, ui.visuals().widgets.hovered.fg_stroke);
+            }
+            let percent = match swatch.share {
+                0.0 => String::new(),
+                share => format!("{:.0}%", share * 100.0),
+            };
+            ui.add(Label::new(egui::RichText::new(percent).small()));
+            let response = response.on_hover_text(&swatch.color.hex);
+            response.context_menu(|ui| {
+                if ui.button("Remove").clicked() {
+                    event = Some(SwatchEvent::Remove(i));
+                    ui.close_menu();
+                }
+            });
+            if response.clicked() {
+                event = Some(SwatchEvent::Pick(swatch.color.clone()));
+            }
+        });
+    }
+    event
+}
+
+// Sample terminal output, each span styled by the slot it shows off.
+const PREVIEW: [&[(&str, Slot)]; 9] = [
+    &[
+        ("user@host", Slot::Ansi(2)),
+        (" ", Slot::Foreground),
+        ("~/waycolor", Slot::Ansi(4)),
+        (" $ ls", Slot::Foreground),
+    ],
+    &[
+        ("Cargo.toml  ", Slot::Foreground),
+        ("src/  target/  ", Slot::Ansi(12)),
+        ("build.sh", Slot::Ansi(10)),
+    ],
+    &[("$ git diff", Slot::Foreground)],
+    &[("-    color: Color32,", Slot::Ansi(1))],
+    &[("+    color: Color,", Slot::Ansi(2))],
+    &[
+        ("warning", Slot::Ansi(11)),
+        (": unused import ", Slot::Foreground),
+        ("error[E0308]", Slot::Ansi(9)),
+    ],
+    &[
+        ("fn ", Slot::Ansi(5)),
+        ("main", Slot::Ansi(6)),
+        ("() { ", Slot::Foreground),
+        ("\"hi\"", Slot::Ansi(3)),
+        (" } ", Slot::Foreground),
+        ("// done", Slot::Ansi(8)),
+    ],
+    &[
+        ("  --> ", Slot::Ansi(14)),
+        ("src/app.rs:42 ", Slot::Ansi(13)),
+        ("note: ", Slot::Ansi(15)),
+        ("ok", Slot::Ansi(7)),
+    ],
+    &[
+        ("$ ", Slot::Foreground),
+        ("selected text", Slot::SelectionForeground),
+        (" ", Slot::Foreground),
+        (" ", Slot::Cursor),
+    ],
+];
+
+// Sample output over the scheme's background, then every slot as a cell:
+// the special colors, the normal colors and the bright ones. Clicking a
+// cell returns its slot.
+fn draw_scheme_preview(ui: &mut egui::Ui, scheme: &Scheme, selected: Option<Slot>) -> Option<Slot> {
+    let font = egui::FontId::monospace(12.0);
+    let mut job = egui::text::LayoutJob::default();
+    for (i, line) in PREVIEW.iter().enumerate() {
+        for (text, slot) in line.iter() {
+            let (color, background) = match slot {
+                Slot::SelectionForeground => (
+                    &scheme.selection_foreground,
+                    scheme.selection_background.to_color32(),
+                ),
+                Slot::Cursor => (&scheme.background, scheme.cursor.to_color32()),
+                slot => (scheme.get(*slot), Color32::TRANSPARENT),
+            };
+            job.append(
+                text,
+                0.0,
+                egui::TextFormat {
+                    font_id: font.clone(),
+                    color: color.to_color32(),
+                    background,
+                    ..Default::default()
+                },
+            );
+        }
+        if i + 1 < PREVIEW.len() {
+            job.append(
+                "\n",
+                0.0,
+                egui::TextFormat::simple(font.clone(), Color32::WHITE),
+            );
+        }
+    }
+    let galley = ui.fonts(|f| f.layout_job(job));
+
+    let cell: f32 = 24.0;
+    let gap = 6.0;
+    let padding = 10.0;
+    let width = (8.0 * (cell + gap) - gap).max(galley.size().x) + 2.0 * padding;
+    let height = galley.size().y + 3.0 * (cell + gap) + 2.0 * padding;
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(width, height), Sense::hover());
+    ui.painter()
+        .rect_filled(rect, 4.0, scheme.background.to_color32());
+    ui.painter().galley(
+        rect.min + Vec2::splat(padding),
+        galley.clone(),
+        Color32::WHITE,
+    );
+
+    let top = rect.min + Vec2::new(padding, padding + galley.size().y + gap);
+    let cells = Slot::SPECIAL
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| ((0, i), *slot))
+        .chain((0..16).map(|i| ((1 + i / 8, i % 8), Slot::Ansi(i))));
+    let mut clicked = None;
+    for ((row, column), slot) in cells {
+        let min = top + Vec2::new(column as f32, row as f32) * (cell + gap);
+        let cell_rect = Rect::from_min_size(min, Vec2::splat(cell));
+        let response = ui.interact(
+            cell_rect,
+            ui.id().with(("scheme", row, column)),
+            Sense::click(),
+        );
+        let color = scheme.get(slot);
+        ui.painter().rect_filled(cell_rect, 2.0, color.to_color32());
+        if response.hovered() || selected == Some(slot) {
+            ui.painter().rect_stroke(
+                cell_rect.expand(2.0),
+                3.0,
+                Stroke::new(1.5, scheme.foreground.to_color32()),
+            );
+        }
+        let hover = format!("{} {}", slot.name(), color.hex);
+        if response.on_hover_text(hover).clicked() {
+            clicked = Some(slot);
+        }
+    }
+    clicked
+}
+
+fn tab_sliders(tab: &str) -> &'static [&'static str] {
+    match tab {
+        "RGB" => &["r", "g", "b"],
+        "HSV" => &["h", "s", "v"],
+        "HSL" => &["h", "sl", "l"],
+        "CMYK" => &["c", "m", "y", "k"],
+        _ => &[],
+    }
+}
+
+// Back to a 0..1 coordinate, wrapping hues and clamping everything else.
+fn plane_coord(plane: &Plane, channel: usize, value: i32) -> f32 {
+    let max = plane.model.maxes()[channel] as i32;
+    let value = if plane.model.is_hue(channel) {
+        value.rem_euclid(max)
+    } else {
+        value.clamp(0, max)
+    };
+    value as f32 / max as f32
+}
+
+// Model, axes and strip of a custom plane. Picking an axis that's already
+// on the other one swaps them.
+fn draw_plane_menu(ui: &mut egui::Ui, plane: &mut Plane) {
+    ui.horizontal(|ui| {
+        for model in Model::ALL {
+            ui.selectable_value(&mut plane.model, model, model.name());
+        }
+    });
+    let names = plane.model.channels().map(|name| name[..1].to_uppercase());
+    for (label, axis) in [("X", 0), ("Y", 1)] {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            for (channel, name) in names.iter().enumerate() {
+                let current = if axis == 0 { plane.x } else { plane.y };
+                if ui.selectable_label(current == channel, name).clicked() {
+                    let (this, other) = if axis == 0 {
+                        (&mut plane.x, &mut plane.y)
+                    } else {
+                        (&mut plane.y, &mut plane.x)
+                    };
+                    if *other == channel {
+                        *other = *this;
+                    }
+                    *this = channel;
+                }
+            }
+        });
+    }
+    let label = format!("{} strip", names[plane.z()]);
+    ui.checkbox(&mut plane.strip, label);
+}
+
+fn snap_to_pixels(ui: &egui::Ui, size: Vec2) -> Vec2 {
+    let ppp = ui.ctx().pixels_per_point();
+    (size * ppp).floor() / ppp
+}
+
+// Arrow keys move by 1% of the channel but at least two units, so Ctrl/Alt
+// moving by a single unit is always finer. Shift moves ten default steps.
+fn key_step(max: u16, modifiers: Modifiers) -> i32 {
+    let default = ((max as f32 * 0.01).round() as i32).max(2);
+    if modifiers.ctrl || modifiers.alt {
+        1
+    } else if modifiers.shift {
+        (default * 10).min(max as i32)
+    } else {
+        default
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_step_fine_is_finer_than_default() {
+        for max in [100, 255, 360] {
+            let fine = key_step(max, Modifiers::CTRL);
+            let default = key_step(max, Modifiers::NONE);
+            let coarse = key_step(max, Modifiers::SHIFT);
+            assert_eq!(fine, 1);
+            assert!(fine < default, "max {max}: {fine} vs {default}");
+            assert!(default < coarse, "max {max}: {default} vs {coarse}");
+        }
+        assert_eq!(key_step(100, Modifiers::NONE), 2);
+        assert_eq!(key_step(255, Modifiers::NONE), 3);
+        assert_eq!(key_step(100, Modifiers::ALT), 1);
+    }
+
+    #[test]
+    fn key_step_shift_is_ten_default_steps() {
+        for max in [100, 255, 360] {
+            let default = key_step(max, Modifiers::NONE);
+            assert_eq!(key_step(max, Modifiers::SHIFT), default * 10, "max {max}");
         }
+        assert_eq!(key_step(100, Modifiers::SHIFT), 20);
+        assert_eq!(key_step(255, Modifiers::SHIFT), 30);
+        assert_eq!(key_step(360, Modifiers::SHIFT), 40);
+        // Small ranges don't jump past their end.
+        assert_eq!(key_step(10, Modifiers::SHIFT), 10);
+        // Ctrl wins over Shift.
+        assert_eq!(key_step(360, Modifiers::CTRL | Modifiers::SHIFT), 1);
     }
 }
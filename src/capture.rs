@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use egui::{ColorImage, TextureHandle, TextureOptions};
+use image::{imageops::FilterType, RgbaImage};
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+use crate::{
+    hyprland::Hyprland,
+    portal::{uri_to_path, Request},
+};
+
+// Captures the whole screen. grim is tried first since it's instant and
+// needs no permission dialog, the portal covers compositors without
+// wlr-screencopy.
+pub fn screenshot() -> Result<ColorImage, String> {
+    grim().or_else(|grim_err| portal().map_err(|err| format!("{grim_err}\n{err}")))
+}
+
+pub fn decode(bytes: &[u8]) -> Result<ColorImage, String> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|err| format!("Failed to decode image: {err}"))?
+        .to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Ok(ColorImage::from_rgba_unmultiplied(size, image.as_raw()))
+}
+
+// The loupe is fullscreen on the focused output, so only that one is
+// captured when Hyprland says which it is.
+fn grim() -> Result<ColorImage, String> {
+    let output = Hyprland::from_env().and_then(|hyprland| hyprland.focused_output().ok());
+    let mut args = vec!["-t", "png", "-l", "0"];
+    if let Some(output) = &output {
+        args.extend(["-o", output]);
+    }
+    args.push("-");
+    decode(&run_grim(&args)?)
+}
+
+// Images can be larger than the GPU takes as a texture, so the texture is
+// scaled down to fit. Sampling still reads the full image.
+pub fn load_texture(ctx: &egui::Context, name: &str, image: &ColorImage) -> TextureHandle {
+    let max = ctx.input(|i| i.max_texture_side);
+    ctx.load_texture(name, fit_texture(image, max), TextureOptions::NEAREST)
+}
+
+fn fit_texture(image: &ColorImage, max: usize) -> ColorImage {
+    let [width, height] = image.size;
+    if width.max(height) <= max {
+        return image.clone();
+    }
+    let scale = max as f32 / width.max(height) as f32;
+    let size = [width, height].map(|side| ((side as f32 * scale) as usize).clamp(1, max));
+    let pixels = RgbaImage::from_raw(width as u32, height as u32, image.as_raw().to_vec())
+        .expect("ColorImage has four bytes per pixel");
+    let scaled = image::imageops::resize(
+        &pixels,
+        size[0] as u32,
+        size[1] as u32,
+        FilterType::Triangle,
+    );
+    ColorImage::from_rgba_premultiplied(size, scaled.as_raw())
+}
+
+// For handing a screenshot to others as a file, see `portal_backend`. Only
+// grim is used here, the portal could end up asking us. The file is created
+// here rather than by grim, readable only by us and never one that already
+// exists, which could be a link planted elsewhere.
+pub fn save_screenshot(path: &Path) -> Result<(), String> {
+    let png = run_grim(&["-t", "png", "-"])?;
+    write_private(path, &png).map_err(|err| format!("Failed to save screenshot: {err}"))
+}
+
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| file.write_all(bytes))
+}
+
+fn run_grim(args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("grim")
+        .args(args)
+        .output()
+        .map_err(|err| format!("Failed to run grim: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "grim failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+// The portal saves the screenshot to a file and hands back its URI. The
+// file is only a means of transport here, so it's removed once read.
+fn portal() -> Result<ColorImage, String> {
+    let path = portal_screenshot().map_err(|err| format!("Screenshot portal failed: {err}"))?;
+    let bytes = std::fs::read(&path).map_err(|err| format!("Failed to read screenshot: {err}"))?;
+    let _ = std::fs::remove_file(&path);
+    decode(&bytes)
+}
+
+fn portal_screenshot() -> Result<PathBuf, String> {
+    let request = Request::new()?;
+    let options = HashMap::from([
+        ("handle_token", Value::from(request.token())),
+        ("interactive", Value::from(false)),
+    ]);
+    let _: OwnedObjectPath = request
+        .proxy("org.freedesktop.portal.Screenshot")?
+        .call("Screenshot", &("", options))
+        .map_err(|err| err.to_string())?;
+
+    let results = request.response()?.ok_or("Screenshot was cancelled")?;
+    let uri = results
+        .get("uri")
+        .and_then(|uri| String::try_from(uri.clone()).ok())
+        .ok_or("Portal returned no URI")?;
+    uri_to_path(&uri).ok_or_else(|| format!("Unsupported URI: {uri}"))
+}
+
+pub fn load_image(path: &Path) -> Result<ColorImage, String> {
+    let bytes =
+        std::fs::read(path).map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    decode(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+
+    use super::*;
+
+    #[test]
+    fn screenshots_are_private_and_never_replace_a_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("waycolor-private-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("shot.png");
+        write_private(&path, b"png").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"png");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(write_private(&path, b"other").is_err());
+
+        let target = dir.join("target");
+        let link = dir.join("link.png");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        assert!(write_private(&link, b"png").is_err());
+        assert!(!target.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn fit_texture_keeps_small_images() {
+        let image = ColorImage::new([40, 20], Color32::RED);
+        assert_eq!(fit_texture(&image, 64).size, [40, 20]);
+    }
+
+    #[test]
+    fn fit_texture_scales_to_the_longest_side() {
+        let image = ColorImage::new([200, 50], Color32::RED);
+        let fitted = fit_texture(&image, 64);
+        assert_eq!(fitted.size, [64, 16]);
+        assert!(fitted.pixels.iter().all(|&pixel| pixel == Color32::RED));
+    }
+}
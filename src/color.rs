@@ -1,8 +1,9 @@
-use egui::{Color32, TextBuffer};
+use egui::Color32;
 
 // r,g,b: 0..255
 // h: 0..360
 // s,v,l,c,y,m,k: 0..100
+// "sl" names HSL saturation where it would clash with HSV's "s".
 #[derive(Debug, Default, Clone)]
 pub struct Color {
     pub r: u16,
@@ -49,12 +50,23 @@ impl Color {
         }
     }
 
+    pub fn from_hsl(h: u16, s: u16, l: u16) -> Self {
+        let (s, v) = hsl_to_hsv(s.min(100) as f32 / 100.0, l.min(100) as f32 / 100.0);
+        Color::from_hsv(h, (s * 100.0).round() as u16, (v * 100.0).round() as u16)
+    }
+
+    pub fn from_cmyk(c: u16, m: u16, y: u16, k: u16) -> Self {
+        let k01 = 1.0 - k.min(100) as f32 / 100.0;
+        let channel = |c: u16| ((1.0 - c.min(100) as f32 / 100.0) * k01 * 255.0).round() as u16;
+        Color::from_rgb(channel(c), channel(m), channel(y))
+    }
+
     pub fn from_hex(hex: String) -> Option<Self> {
         if hex.len() != 7 {
             return None;
         }
         if let Some(stripped) = hex.strip_prefix('#') {
-            let [_, r, g, b] = match u32::from_str_radix(stripped.as_str(), 16) {
+            let [_, r, g, b] = match u32::from_str_radix(stripped, 16) {
                 Ok(r) => r.to_be_bytes(),
                 Err(_) => return None,
             };
@@ -72,13 +84,6 @@ impl Color {
         None
     }
 
-    pub fn dim(&self) -> Self {
-        let h = (self.h + 180) % 360;
-        let s = 30;
-        let v = 100 - self.v;
-        Color::from_hsv(h, s, v)
-    }
-
     pub fn inv(&self) -> Self {
         let h = (self.h + 180) % 360;
         let s = 85;
@@ -86,6 +91,18 @@ impl Color {
         Color::from_hsv(h, s, v)
     }
 
+    // WCAG relative luminance.
+    pub fn luminance(&self) -> f32 {
+        let [r, g, b] = [self.r, self.g, self.b].map(|c| srgb_to_linear(c as f32 / 255.0));
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    // WCAG contrast ratio, 1..21.
+    pub fn contrast(&self, other: &Color) -> f32 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
     pub fn to_color32(&self) -> Color32 {
         Color32::from_rgb(self.r as u8, self.g as u8, self.b as u8)
     }
@@ -98,6 +115,7 @@ impl Color {
             "h" => self.h,
             "s" => self.s,
             "v" => self.v,
+            "sl" | "l" | "c" | "m" | "y" | "k" => (self.float_by_name(name) * 100.0).round() as u16,
             _ => 0,
         }
     }
@@ -110,9 +128,46 @@ impl Color {
             "h" => self.h as f32 / 360.0,
             "s" => self.s as f32 / 100.0,
             "v" => self.v as f32 / 100.0,
+            "sl" | "l" => {
+                let (s, l) = hsv_to_hsl(self.s as f32 / 100.0, self.v as f32 / 100.0);
+                if name == "l" {
+                    l
+                } else {
+                    s
+                }
+            }
+            "c" | "m" | "y" | "k" => {
+                let [c, m, y, k] = self.cmyk();
+                match name {
+                    "c" => c,
+                    "m" => m,
+                    "y" => y,
+                    _ => k,
+                }
+            }
             _ => 0.0,
         }
     }
+
+    // CMYK in 0..1. Black has no ink other than K.
+    fn cmyk(&self) -> [f32; 4] {
+        let [r, g, b] = [self.r, self.g, self.b].map(|c| c as f32 / 255.0);
+        let k = 1.0 - r.max(g).max(b);
+        if k >= 1.0 {
+            return [0.0, 0.0, 0.0, 1.0];
+        }
+        let [c, m, y] = [r, g, b].map(|c| (1.0 - c - k) / (1.0 - k));
+        [c, m, y, k]
+    }
+}
+
+pub fn max_by_name(name: &str) -> u16 {
+    match name {
+        "r" | "g" | "b" => 255,
+        "h" => 360,
+        "s" | "v" | "sl" | "l" | "c" | "m" | "y" | "k" => 100,
+        _ => 0,
+    }
 }
 
 pub fn rgb_to_hsv(r: u16, g: u16, b: u16) -> (u16, u16, u16) {
@@ -178,6 +233,213 @@ pub fn rgb_to_cymk(r: u16, g: u16, b: u16) -> (u16, u16, u16, u16) {
     (c as u16, y as u16, m as u16, (k * 100.0) as u16)
 }
 
+// Saturation and lightness/value conversions between HSL and HSV, all in
+// 0..1. Hue is shared between the two.
+pub fn hsl_to_hsv(s: f32, l: f32) -> (f32, f32) {
+    let v = l + s * l.min(1.0 - l);
+    let s = if v > 0.0 { 2.0 * (1.0 - l / v) } else { 0.0 };
+    (s, v)
+}
+
+pub fn hsv_to_hsl(s: f32, v: f32) -> (f32, f32) {
+    let l = v * (1.0 - s * 0.5);
+    let s = if l > 0.0 && l < 1.0 {
+        (v - l) / l.min(1.0 - l)
+    } else {
+        0.0
+    };
+    (s, l)
+}
+
+// Chroma that maps to 1.0 on a normalized OKLCH axis. The most saturated
+// sRGB colors stay just below it.
+pub const OKLCH_MAX_CHROMA: f32 = 0.37;
+
+// Color models a gradient plane can be built from. Channel coordinates are
+// normalized to 0..1 in the order of `channels`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Model {
+    Rgb,
+    Hsv,
+    Hsl,
+    Oklch,
+}
+
+impl Model {
+    pub const ALL: [Model; 4] = [Model::Rgb, Model::Hsv, Model::Hsl, Model::Oklch];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Rgb => "RGB",
+            Model::Hsv => "HSV",
+            Model::Hsl => "HSL",
+            Model::Oklch => "OKLCH",
+        }
+    }
+
+    pub fn channels(&self) -> [&'static str; 3] {
+        match self {
+            Model::Rgb => ["r", "g", "b"],
+            Model::Hsv => ["h", "s", "v"],
+            Model::Hsl => ["h", "sl", "l"],
+            Model::Oklch => ["L", "C", "h"],
+        }
+    }
+
+    // Range each channel is shown and snapped in.
+    pub fn maxes(&self) -> [u16; 3] {
+        match self {
+            Model::Rgb => [255, 255, 255],
+            Model::Hsv | Model::Hsl => [360, 100, 100],
+            Model::Oklch => [100, 100, 360],
+        }
+    }
+
+    // Whether the channel is an angle and wraps around instead of clamping.
+    pub fn is_hue(&self, channel: usize) -> bool {
+        matches!(
+            (self, channel),
+            (Model::Hsv | Model::Hsl, 0) | (Model::Oklch, 2)
+        )
+    }
+
+    pub fn coords(&self, color: &Color) -> [f32; 3] {
+        let h = color.float_by_name("h");
+        let s = color.float_by_name("s");
+        let v = color.float_by_name("v");
+        match self {
+            Model::Rgb => ["r", "g", "b"].map(|name| color.float_by_name(name)),
+            Model::Hsv => [h, s, v],
+            Model::Hsl => {
+                let (s, l) = hsv_to_hsl(s, v);
+                [h, s, l]
+            }
+            Model::Oklch => {
+                let [l, c, h] = rgb_to_oklch(["r", "g", "b"].map(|name| color.float_by_name(name)));
+                [l, (c / OKLCH_MAX_CHROMA).min(1.0), h]
+            }
+        }
+    }
+
+    // sRGB in 0..1. OKLCH coordinates can fall outside the gamut, in which
+    // case some channels end up outside 0..1.
+    pub fn to_rgb(self, [a, b, c]: [f32; 3]) -> [f32; 3] {
+        match self {
+            Model::Rgb => [a, b, c],
+            Model::Hsv => hsv_to_rgb01(a, b, c),
+            Model::Hsl => {
+                let (s, v) = hsl_to_hsv(b, c);
+                hsv_to_rgb01(a, s, v)
+            }
+            Model::Oklch => oklch_to_rgb([a, b * OKLCH_MAX_CHROMA, c]),
+        }
+    }
+
+    pub fn color(&self, coords: [f32; 3]) -> Color {
+        match self {
+            Model::Hsv | Model::Hsl => {
+                let [h, s, v] = match self {
+                    Model::Hsl => {
+                        let (s, v) = hsl_to_hsv(coords[1], coords[2]);
+                        [coords[0], s, v]
+                    }
+                    _ => coords,
+                };
+                Color::from_hsv(
+                    (h * 360.0).round().rem_euclid(360.0) as u16,
+                    (s * 100.0).round().clamp(0.0, 100.0) as u16,
+                    (v * 100.0).round().clamp(0.0, 100.0) as u16,
+                )
+            }
+            Model::Rgb | Model::Oklch => {
+                let [r, g, b] = self
+                    .to_rgb(coords)
+                    .map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u16);
+                Color::from_rgb(r, g, b)
+            }
+        }
+    }
+}
+
+// OKLCH with lightness and chroma in OKLab units and hue in 0..1, to and
+// from sRGB in 0..1.
+pub fn rgb_to_oklch(rgb: [f32; 3]) -> [f32; 3] {
+    let [lightness, a, b] = rgb_to_oklab(rgb).map(|c| c as f64);
+    let chroma = a.hypot(b);
+    let hue = if chroma > 1e-4 {
+        (b.atan2(a) / std::f64::consts::TAU).rem_euclid(1.0)
+    } else {
+        0.0
+    };
+    [lightness, chroma, hue].map(|c| c as f32)
+}
+
+pub fn oklch_to_rgb(lch: [f32; 3]) -> [f32; 3] {
+    let [lightness, chroma, hue] = lch.map(|c| c as f64);
+    let angle = hue * std::f64::consts::TAU;
+    oklab_to_rgb([lightness, chroma * angle.cos(), chroma * angle.sin()].map(|c| c as f32))
+}
+
+// The matrices are done in f64 to keep their precision.
+pub fn rgb_to_oklab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|c| srgb_to_linear(c) as f64);
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+    .map(|c| c as f32)
+}
+
+pub fn oklab_to_rgb(lab: [f32; 3]) -> [f32; 3] {
+    let [lightness, a, b] = lab.map(|c| c as f64);
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+    .map(|c| linear_to_srgb(c as f32))
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// HSV in 0..1 to sRGB in 0..1, the hue wraps around.
+pub(crate) fn hsv_to_rgb01(h: f32, s: f32, v: f32) -> [f32; 3] {
+    let h = h.rem_euclid(1.0) * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = v - c;
+    let [r, g, b] = match h as u32 {
+        0 => [c, x, 0.0],
+        1 => [x, c, 0.0],
+        2 => [0.0, c, x],
+        3 => [0.0, x, c],
+        4 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    [r + m, g + m, b + m]
+}
+
 fn hsv_to_rbg(h: u16, s: u16, v: u16) -> (u16, u16, u16) {
     let s01 = s as f32 / 100.0;
     let v01 = v as f32 / 100.0;
@@ -204,5 +466,139 @@ fn hsv_to_rbg(h: u16, s: u16, v: u16) -> (u16, u16, u16) {
 }
 
 fn get_hex(r: u16, g: u16, b: u16) -> String {
-    format!("#{:X?}{:X?}{:X?}", r, g, b)
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Black, white, the primaries and secondaries.
+    const CORNERS: [[u16; 3]; 8] = [
+        [0, 0, 0],
+        [255, 255, 255],
+        [255, 0, 0],
+        [0, 255, 0],
+        [0, 0, 255],
+        [255, 255, 0],
+        [0, 255, 255],
+        [255, 0, 255],
+    ];
+
+    fn corners() -> impl Iterator<Item = Color> {
+        CORNERS
+            .into_iter()
+            .map(|[r, g, b]| Color::from_rgb(r, g, b))
+    }
+
+    fn grays() -> impl Iterator<Item = Color> {
+        [1, 64, 128, 200, 254]
+            .into_iter()
+            .map(|c| Color::from_rgb(c, c, c))
+    }
+
+    // HSL and CMYK values are whole percents, so a channel can be off by
+    // one step of 255 / 100.
+    fn assert_close(a: &Color, b: &Color) {
+        for (x, y) in [(a.r, b.r), (a.g, b.g), (a.b, b.b)] {
+            assert!(x.abs_diff(y) <= 2, "{} != {}", a.hex, b.hex);
+        }
+    }
+
+    fn hsl(color: &Color) -> Color {
+        let [h, s, l] = ["h", "sl", "l"].map(|name| color.value_by_name(name));
+        Color::from_hsl(h, s, l)
+    }
+
+    fn cmyk(color: &Color) -> Color {
+        let [c, m, y, k] = ["c", "m", "y", "k"].map(|name| color.value_by_name(name));
+        Color::from_cmyk(c, m, y, k)
+    }
+
+    #[test]
+    fn hsl_round_trip() {
+        for color in corners() {
+            assert_eq!(hsl(&color).hex, color.hex);
+        }
+        for gray in grays() {
+            assert_eq!(gray.value_by_name("sl"), 0);
+            assert_close(&hsl(&gray), &gray);
+        }
+        let red = Color::from_rgb(255, 0, 0);
+        assert_eq!(["h", "sl", "l"].map(|n| red.value_by_name(n)), [0, 100, 50]);
+        assert_close(&Color::from_hsl(120, 100, 25), &Color::from_rgb(0, 128, 0));
+        assert_eq!(Color::from_hsl(0, 100, 100).hex, "#FFFFFF");
+    }
+
+    #[test]
+    fn cmyk_round_trip() {
+        for color in corners().chain(grays()) {
+            assert_close(&cmyk(&color), &color);
+        }
+        for color in corners() {
+            assert_eq!(cmyk(&color).hex, color.hex);
+        }
+        let cmyk_of = |r, g, b| Color::from_rgb(r, g, b).cmyk().map(|c| (c * 100.0).round());
+        assert_eq!(cmyk_of(255, 0, 0), [0.0, 100.0, 100.0, 0.0]);
+        assert_eq!(cmyk_of(255, 255, 0), [0.0, 0.0, 100.0, 0.0]);
+        assert_eq!(cmyk_of(0, 0, 0), [0.0, 0.0, 0.0, 100.0]);
+        assert_eq!(cmyk_of(128, 64, 0), [0.0, 50.0, 100.0, 50.0]);
+        assert_eq!(Color::from_cmyk(0, 100, 100, 0).hex, "#FF0000");
+        assert_eq!(Color::from_cmyk(0, 0, 0, 100).hex, "#000000");
+    }
+
+    #[test]
+    fn oklab_round_trip() {
+        for color in corners().chain(grays()) {
+            let rgb = ["r", "g", "b"].map(|name| color.float_by_name(name));
+            let back = oklab_to_rgb(rgb_to_oklab(rgb));
+            for (a, b) in rgb.into_iter().zip(back) {
+                assert!((a - b).abs() < 1e-4, "{}: {rgb:?} != {back:?}", color.hex);
+            }
+            let back = oklch_to_rgb(rgb_to_oklch(rgb));
+            for (a, b) in rgb.into_iter().zip(back) {
+                assert!((a - b).abs() < 1e-4, "{}: {rgb:?} != {back:?}", color.hex);
+            }
+        }
+        let [l, a, b] = rgb_to_oklab([1.0, 1.0, 1.0]);
+        assert!((l - 1.0).abs() < 1e-4 && a.abs() < 1e-4 && b.abs() < 1e-4);
+        for gray in grays() {
+            let [_, chroma, hue] = Model::Oklch.coords(&gray);
+            assert!(chroma < 1e-3);
+            assert_eq!(hue, 0.0);
+        }
+    }
+
+    #[test]
+    fn models_round_trip() {
+        for model in Model::ALL {
+            for color in corners() {
+                let back = model.color(model.coords(&color));
+                assert_eq!(back.hex, color.hex, "{}", model.name());
+            }
+            for gray in grays() {
+                assert_close(&model.color(model.coords(&gray)), &gray);
+            }
+        }
+    }
+
+    #[test]
+    fn oklch_chroma_is_normalized_and_clamped() {
+        // The most saturated sRGB colors fit below OKLCH_MAX_CHROMA.
+        for color in corners() {
+            let [_, chroma, _] = Model::Oklch.coords(&color);
+            assert!((0.0..1.0).contains(&chroma), "{}: {chroma}", color.hex);
+        }
+        let [_, blue, _] = Model::Oklch.coords(&Color::from_rgb(0, 0, 255));
+        assert!((blue * OKLCH_MAX_CHROMA - 0.313).abs() < 1e-3);
+
+        // Full chroma is outside sRGB, colors made from it are clamped.
+        let coords = [0.6, 1.0, 0.4];
+        let rgb = Model::Oklch.to_rgb(coords);
+        assert!(rgb.iter().any(|c| !(0.0..=1.0).contains(c)));
+        let color = Model::Oklch.color(coords);
+        let clamped = rgb.map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u16);
+        assert_eq!([color.r, color.g, color.b], clamped);
+        assert_eq!(Model::Oklch.color([1.0, 1.0, 0.0]).hex.len(), 7);
+    }
 }
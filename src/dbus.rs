@@ -0,0 +1,242 @@
+use std::sync::mpsc::Sender;
+
+use zbus::{
+    blocking::{Connection, ConnectionBuilder},
+    dbus_interface, fdo, SignalContext,
+};
+
+use crate::{
+    color::Color,
+    ipc::{self, Command, Request},
+};
+
+pub const NAME: &str = "io.github.WayColor";
+pub const PATH: &str = "/io/github/WayColor";
+
+// The same commands as the control socket, for applications that would
+// rather use the session bus. Calls wait for the app on the blocking thread
+// pool so a pending `Pick` doesn't hold up the others.
+struct Interface {
+    requests: Sender<Request>,
+    ctx: egui::Context,
+}
+
+impl Interface {
+    async fn request(&self, command: Command) -> fdo::Result<String> {
+        request(&self.requests, &self.ctx, command)
+            .await
+            .map_err(fdo::Error::Failed)
+    }
+}
+
+#[dbus_interface(name = "io.github.WayColor")]
+impl Interface {
+    async fn get_color(&self) -> fdo::Result<String> {
+        self.request(Command::GetColor).await
+    }
+
+    // Returns the color as it was applied, normalized to #RRGGBB.
+    async fn set_color(&self, color: &str) -> fdo::Result<String> {
+        let color = ipc::parse_color(color).map_err(fdo::Error::InvalidArgs)?;
+        self.request(Command::SetColor(color)).await
+    }
+
+    // Returns once the user has picked, or fails if they cancelled.
+    async fn pick(&self) -> fdo::Result<String> {
+        self.request(Command::Pick).await
+    }
+
+    #[dbus_interface(signal)]
+    async fn color_changed(ctxt: &SignalContext<'_>, color: &str) -> zbus::Result<()>;
+}
+
+// Hands a command to the app and waits for its reply without blocking the
+// connection's executor.
+pub async fn request(
+    requests: &Sender<Request>,
+    ctx: &egui::Context,
+    command: Command,
+) -> Result<String, String> {
+    let (request, receiver) = Request::new(command);
+    requests
+        .send(request)
+        .map_err(|_| String::from("WayColor is shutting down"))?;
+    ctx.request_repaint();
+    blocking::unblock(move || receiver.recv())
+        .await
+        .unwrap_or_else(|_| Err(String::from("WayColor is shutting down")))
+}
+
+pub struct Service {
+    connection: Connection,
+    last: String,
+}
+
+impl Service {
+    pub fn start(ctx: egui::Context, requests: Sender<Request>) -> Result<Self, String> {
+        Self::serve(ConnectionBuilder::session(), ctx, requests)
+    }
+
+    fn serve(
+        builder: zbus::Result<ConnectionBuilder<'static>>,
+        ctx: egui::Context,
+        requests: Sender<Request>,
+    ) -> Result<Self, String> {
+        let connection = builder
+            .and_then(|builder| builder.serve_at(PATH, Interface { requests, ctx }))
+            .and_then(|builder| builder.name(NAME))
+            .and_then(|builder| builder.build())
+            .map_err(|err| err.to_string())?;
+        Ok(Self {
+            connection,
+            last: String::new(),
+        })
+    }
+
+    // Called every frame, only signals when the color actually changed.
+    pub fn notify(&mut self, color: &Color) -> Result<(), String> {
+        if self.last == color.hex {
+            return Ok(());
+        }
+        self.last.clone_from(&color.hex);
+        SignalContext::new(self.connection.inner(), PATH)
+            .and_then(|ctxt| zbus::block_on(Interface::color_changed(&ctxt, &color.hex)))
+            .map_err(|err| format!("Failed to signal color change: {err}"))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command as Process, Stdio},
+        sync::mpsc,
+        time::Duration,
+    };
+
+    use zbus::blocking::Proxy;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // A private session bus for the test, stopped when dropped.
+    pub(crate) struct Bus {
+        daemon: Child,
+        pub(crate) address: String,
+    }
+
+    impl Bus {
+        pub(crate) fn start() -> Option<Self> {
+            let mut daemon = Process::new("dbus-daemon")
+                .args(["--session", "--print-address", "--nofork"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+
+        pub(crate) fn connect(&self) -> Connection {
+            ConnectionBuilder::address(self.address.as_str())
+                .and_then(|builder| builder.build())
+                .unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    // Answers like the app would: the first pick succeeds, later ones are
+    // cancelled.
+    pub(crate) fn fake_app(requests: mpsc::Receiver<Request>) {
+        std::thread::spawn(move || {
+            let mut color = String::from("#161621");
+            let mut picks = 0;
+            for request in requests {
+                let reply = match &request.command {
+                    Command::GetColor => Ok(color.clone()),
+                    Command::SetColor(new) => {
+                        color.clone_from(&new.hex);
+                        Ok(color.clone())
+                    }
+                    Command::Pick => {
+                        picks += 1;
+                        match picks {
+                            1 => Ok(String::from("#123456")),
+                            _ => Err(String::from(ipc::CANCELLED)),
+                        }
+                    }
+                    _ => Err(String::from("Unexpected command")),
+                };
+                request.reply(reply);
+            }
+        });
+    }
+
+    fn call<A>(proxy: &Proxy, method: &str, args: &A) -> Result<String, String>
+    where
+        A: zbus::export::serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        proxy
+            .call::<_, _, String>(method, args)
+            .map_err(|err| match err {
+                zbus::Error::MethodError(name, _, _) => name.to_string(),
+                err => err.to_string(),
+            })
+    }
+
+    #[test]
+    fn interface_and_signal() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon isn't available, skipping");
+            return;
+        };
+        let (sender, requests) = mpsc::channel();
+        fake_app(requests);
+        let builder = ConnectionBuilder::address(bus.address.as_str());
+        let mut service = Service::serve(builder, egui::Context::default(), sender).unwrap();
+
+        let client = bus.connect();
+        let proxy = Proxy::new(&client, NAME, PATH, NAME).unwrap();
+        assert_eq!(call(&proxy, "GetColor", &()), Ok(String::from("#161621")));
+        assert_eq!(call(&proxy, "SetColor", &("#aabbcc",)), Ok(String::from("#AABBCC")));
+        assert_eq!(call(&proxy, "GetColor", &()), Ok(String::from("#AABBCC")));
+        assert_eq!(
+            call(&proxy, "SetColor", &("not a color",)),
+            Err(String::from("org.freedesktop.DBus.Error.InvalidArgs"))
+        );
+        assert_eq!(call(&proxy, "Pick", &()), Ok(String::from("#123456")));
+        assert_eq!(
+            call(&proxy, "Pick", &()),
+            Err(String::from("org.freedesktop.DBus.Error.Failed"))
+        );
+
+        let (signals_sender, signals) = mpsc::channel();
+        let mut changes = proxy.receive_signal("ColorChanged").unwrap();
+        std::thread::spawn(move || {
+            for message in &mut changes {
+                let _ = signals_sender.send(message.body::<String>().unwrap());
+            }
+        });
+        let color = Color::from_rgb(1, 2, 3);
+        service.notify(&color).unwrap();
+        // Unchanged colors don't signal again.
+        service.notify(&color).unwrap();
+        service.notify(&Color::from_rgb(4, 5, 6)).unwrap();
+        assert_eq!(signals.recv_timeout(TIMEOUT), Ok(String::from("#010203")));
+        assert_eq!(signals.recv_timeout(TIMEOUT), Ok(String::from("#040506")));
+        assert!(signals.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::{iter::Peekable, str::Chars};
+
+// Evaluates slider input against the current channel value:
+//   128+16, (255-32)/2   plain arithmetic
+//   +10, -5, *1.1, /2    relative to the current value
+//   50%                  percentage of the channel range
+//   0.5                  a lone fraction is a normalized value
+//   200deg, 200°         degrees, hue only
+pub fn evaluate(text: &str, current: f32, max: f32, degrees: bool) -> Result<f32, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(String::from("Empty value"));
+    }
+    if let Ok(value) = text.parse::<f32>() {
+        if text.contains('.') && (0.0..=1.0).contains(&value) {
+            return Ok(value * max);
+        }
+    }
+    let text = if text.starts_with(['+', '-', '*', '/']) {
+        format!("{current}{text}")
+    } else {
+        text.to_string()
+    };
+
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        max,
+        degrees,
+    };
+    let value = parser.expression()?;
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        Some(c) => Err(format!("Unexpected '{c}'")),
+        None if value.is_finite() => Ok(value),
+        None => Err(String::from("Result is not a number")),
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    max: f32,
+    degrees: bool,
+}
+
+impl Parser<'_> {
+    fn expression(&mut self) -> Result<f32, String> {
+        let mut value = self.term()?;
+        while let Some(op) = self.next_if(|c| c == '+' || c == '-') {
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f32, String> {
+        let mut value = self.factor()?;
+        while let Some(op) = self.next_if(|c| c == '*' || c == '/') {
+            let rhs = self.factor()?;
+            value = if op == '*' { value * rhs } else { value / rhs };
+        }
+        Ok(value)
+    }
+
+    fn factor(&mut self) -> Result<f32, String> {
+        if self.next_if(|c| c == '-').is_some() {
+            return Ok(-self.factor()?);
+        }
+        if self.next_if(|c| c == '(').is_some() {
+            let value = self.expression()?;
+            return match self.next_if(|c| c == ')') {
+                Some(_) => Ok(value),
+                None => Err(String::from("Missing ')'")),
+            };
+        }
+        let value = self.number()?;
+        self.unit(value)
+    }
+
+    fn number(&mut self) -> Result<f32, String> {
+        self.skip_whitespace();
+        let mut digits = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+            digits.push(c);
+        }
+        match self.chars.peek() {
+            _ if !digits.is_empty() => digits
+                .parse::<f32>()
+                .map_err(|_| format!("Invalid number '{digits}'")),
+            Some(c) => Err(format!("Unexpected '{c}'")),
+            None => Err(String::from("Missing value")),
+        }
+    }
+
+    fn unit(&mut self, value: f32) -> Result<f32, String> {
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'%').is_some() {
+            return Ok(value * 0.01 * self.max);
+        }
+        let is_degrees = if self.chars.next_if_eq(&'°').is_some() {
+            true
+        } else if self.chars.peek() == Some(&'d') {
+            let unit: String = self.chars.by_ref().take(3).collect();
+            if unit != "deg" {
+                return Err(format!("Unknown unit '{unit}'"));
+            }
+            true
+        } else {
+            false
+        };
+        match is_degrees {
+            true if !self.degrees => Err(String::from("Degrees only apply to hue")),
+            _ => Ok(value),
+        }
+    }
+
+    fn next_if(&mut self, f: impl Fn(char) -> bool) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.next_if(|c| f(*c))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str) -> Result<f32, String> {
+        evaluate(text, 100.0, 255.0, false)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("128+16"), Ok(144.0));
+        assert_eq!(eval("(255-32)/2"), Ok(111.5));
+        assert_eq!(eval("2*3+4"), Ok(10.0));
+        assert_eq!(eval("2*(3+4)"), Ok(14.0));
+        assert_eq!(eval("-5+10"), Ok(105.0));
+    }
+
+    #[test]
+    fn relative() {
+        assert_eq!(eval("+10"), Ok(110.0));
+        assert_eq!(eval("-5"), Ok(95.0));
+        assert_eq!(eval("*1.5"), Ok(150.0));
+        assert_eq!(eval("/4"), Ok(25.0));
+    }
+
+    #[test]
+    fn percent_and_fractions() {
+        assert_eq!(eval("50%"), Ok(127.5));
+        assert_eq!(eval("0.5"), Ok(127.5));
+        assert_eq!(eval("1"), Ok(1.0));
+        assert!((evaluate("10%", 0.0, 100.0, false).unwrap() - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn degrees() {
+        assert_eq!(evaluate("200deg", 0.0, 360.0, true), Ok(200.0));
+        assert_eq!(evaluate("90°", 0.0, 360.0, true), Ok(90.0));
+        assert!(eval("200deg").is_err());
+    }
+
+    #[test]
+    fn bad_input() {
+        assert!(eval("").is_err());
+        assert!(eval("   ").is_err());
+        assert!(eval("abc").is_err());
+        assert!(eval("1+").is_err());
+        assert!(eval("(1+2").is_err());
+        assert!(eval("1 2").is_err());
+        assert!(eval("10px").is_err());
+        assert!(eval("1/0").is_err());
+        assert!(eval("1..2").is_err());
+    }
+}
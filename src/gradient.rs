@@ -1,70 +1,288 @@
-use eframe::glow;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
-use crate::color::Color;
+use eframe::{egui_glow, glow};
+use egui::{Painter, Rect, Vec2};
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::{
+    color::{max_by_name, Color, Model, OKLCH_MAX_CHROMA},
+    mesh::MeshPainter,
+    shape::{Plane, Shape, RING_WIDTH, STRIP_GAP, STRIP_WIDTH},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GradientType {
-    Gradient,
+    Gradient(Shape),
     Slider(String),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Renderer {
+    Gpu,
+    Mesh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dither {
+    #[default]
+    Off,
+    Ordered,
+    BlueNoise,
+}
+
+impl Dither {
+    pub const ALL: [Dither; 3] = [Dither::Off, Dither::Ordered, Dither::BlueNoise];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dither::Off => "No dithering",
+            Dither::Ordered => "Ordered dithering",
+            Dither::BlueNoise => "Blue noise dithering",
+        }
+    }
+}
+
+// How gradients are finished off after shading. `quantize` shows only the
+// colors the picker can produce, with no dithering on top.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RenderOptions {
+    pub dither: Dither,
+    pub quantize: bool,
+}
+
+impl RenderOptions {
+    // Packed into the shaders' `params` uniform.
+    pub fn params(&self) -> [f32; 4] {
+        let dither = match self.dither {
+            Dither::Off => 0.0,
+            Dither::Ordered => 1.0,
+            Dither::BlueNoise => 2.0,
+        };
+        [dither, self.quantize as u8 as f32, 0.0, 0.0]
+    }
+}
+
+// Implemented once per rendering backend so `App` doesn't need to know
+// whether gradients come from glow, wgpu or plain egui meshes.
+pub trait GradientPainter {
+    fn name(&self) -> &'static str;
+    fn paint(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        gtype: &GradientType,
+        color: Color,
+        options: RenderOptions,
+    );
+    fn destroy(&self, _gl: &glow::Context) {}
+    // A gradient that failed when first used after `new`, reported once.
+    // It's drawn with meshes from then on.
+    fn take_error(&self) -> Option<String> {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum GradientError {
+    Create(String),
+    Compile {
+        gtype: GradientType,
+        stage: &'static str,
+        log: String,
+    },
+    Link {
+        gtype: GradientType,
+        log: String,
+    },
+}
+
+impl std::fmt::Display for GradientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GradientError::Create(err) => write!(f, "Failed to create GL object: {err}"),
+            GradientError::Compile { gtype, stage, log } => {
+                write!(f, "Failed to compile {stage} shader for {gtype:?}: {log}")
+            }
+            GradientError::Link { gtype, log } => {
+                write!(f, "Failed to link program for {gtype:?}: {log}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GradientError {}
+
+// Current color in the coordinates of a plane's model, 0..1 per channel.
+pub fn model_coords(gtype: &GradientType, color: &Color) -> [f32; 3] {
+    match gtype {
+        GradientType::Gradient(Shape::Plane(plane)) => plane.model.coords(color),
+        _ => [0.0; 3],
+    }
+}
+
+// Planes can use any pair of channels, so only the gradients passed to
+// `new` are compiled up front and the rest on first use. `None` marks a
+// gradient that failed to compile so it isn't retried every frame.
+pub struct GlowPainter {
+    gradients: Arc<Mutex<HashMap<GradientType, Option<Gradient>>>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl GlowPainter {
+    pub fn new(gl: &glow::Context, gtypes: &[GradientType]) -> Result<Self, GradientError> {
+        let painter = Self {
+            gradients: Arc::new(Mutex::new(HashMap::new())),
+            error: Arc::new(Mutex::new(None)),
+        };
+        for gtype in gtypes {
+            match Gradient::new(gl, gtype.clone()) {
+                Ok(gradient) => {
+                    painter
+                        .gradients
+                        .lock()
+                        .unwrap()
+                        .insert(gtype.clone(), Some(gradient));
+                }
+                Err(err) => {
+                    painter.destroy(gl);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(painter)
+    }
+}
+
+impl GradientPainter for GlowPainter {
+    fn name(&self) -> &'static str {
+        "OpenGL"
+    }
+
+    fn paint(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        gtype: &GradientType,
+        color: Color,
+        options: RenderOptions,
+    ) {
+        if let Some(None) = self.gradients.lock().unwrap().get(gtype) {
+            MeshPainter.paint(painter, rect, gtype, color, options);
+            return;
+        }
+        let gradients = self.gradients.clone();
+        let error = self.error.clone();
+        let ctx = painter.ctx().clone();
+        let gtype = gtype.clone();
+        let model = model_coords(&gtype, &color);
+        let params = options.params();
+        painter.add(egui::PaintCallback {
+            rect,
+            callback: Arc::new(egui_glow::CallbackFn::new(move |_info, painter| {
+                let mut gradients = gradients.lock().unwrap();
+                let gradient =
+                    gradients.entry(gtype.clone()).or_insert_with(|| {
+                        match Gradient::new(painter.gl(), gtype.clone()) {
+                            Ok(gradient) => Some(gradient),
+                            Err(err) => {
+                                *error.lock().unwrap() = Some(err.to_string());
+                                ctx.request_repaint();
+                                None
+                            }
+                        }
+                    });
+                if let Some(gradient) = gradient {
+                    gradient.paint(painter.gl(), color.clone(), model, params, rect.size());
+                }
+            })),
+        });
+    }
+
+    fn destroy(&self, gl: &glow::Context) {
+        for gradient in self.gradients.lock().unwrap().values().flatten() {
+            gradient.destroy(gl);
+        }
+    }
+
+    fn take_error(&self) -> Option<String> {
+        self.error.lock().unwrap().take()
+    }
+}
+
 #[derive(Debug)]
 pub struct Gradient {
-    gtype: GradientType,
     program: glow::Program,
     vertex_array: glow::VertexArray,
 }
 
 impl Gradient {
-    pub fn new(gl: &glow::Context, gtype: GradientType) -> Self {
+    pub fn new(gl: &glow::Context, gtype: GradientType) -> Result<Self, GradientError> {
         use glow::HasContext as _;
 
         unsafe {
-            let program = gl.create_program().expect("Cannot create program");
+            let program = gl.create_program().map_err(GradientError::Create)?;
             let (vertex_shader_source, fragment_shader_source) = get_shader_sources(&gtype);
             let shader_sources = [
                 (glow::VERTEX_SHADER, vertex_shader_source),
                 (glow::FRAGMENT_SHADER, fragment_shader_source),
             ];
 
-            let shaders: Vec<_> = shader_sources
-                .iter()
-                .map(|(shader_type, shader_source)| {
-                    let shader = gl
-                        .create_shader(*shader_type)
-                        .expect("Cannot create shader");
-                    gl.shader_source(shader, shader_source);
-                    gl.compile_shader(shader);
-                    assert!(
-                        gl.get_shader_compile_status(shader),
-                        "Failed to compile {shader_type}: {}",
-                        gl.get_shader_info_log(shader)
-                    );
-                    gl.attach_shader(program, shader);
-                    shader
-                })
-                .collect();
-
-            gl.link_program(program);
-            assert!(
-                gl.get_program_link_status(program),
-                "{}",
-                gl.get_program_info_log(program)
-            );
+            let mut shaders = Vec::new();
+            let mut result = Ok(());
+            for (shader_type, shader_source) in shader_sources.iter() {
+                let shader = match gl.create_shader(*shader_type) {
+                    Ok(shader) => shader,
+                    Err(err) => {
+                        result = Err(GradientError::Create(err));
+                        break;
+                    }
+                };
+                shaders.push(shader);
+                gl.shader_source(shader, shader_source);
+                gl.compile_shader(shader);
+                if !gl.get_shader_compile_status(shader) {
+                    result = Err(GradientError::Compile {
+                        gtype: gtype.clone(),
+                        stage: if *shader_type == glow::VERTEX_SHADER {
+                            "vertex"
+                        } else {
+                            "fragment"
+                        },
+                        log: gl.get_shader_info_log(shader),
+                    });
+                    break;
+                }
+                gl.attach_shader(program, shader);
+            }
+
+            if result.is_ok() {
+                gl.link_program(program);
+                if !gl.get_program_link_status(program) {
+                    result = Err(GradientError::Link {
+                        gtype: gtype.clone(),
+                        log: gl.get_program_info_log(program),
+                    });
+                }
+            }
 
             for shader in shaders {
                 gl.detach_shader(program, shader);
                 gl.delete_shader(shader);
             }
 
-            let vertex_array = gl
-                .create_vertex_array()
-                .expect("Cannot create vertex array");
-
-            Self {
-                gtype,
-                program,
-                vertex_array,
+            let vertex_array =
+                result.and_then(|_| gl.create_vertex_array().map_err(GradientError::Create));
+            match vertex_array {
+                Ok(vertex_array) => Ok(Self {
+                    program,
+                    vertex_array,
+                }),
+                Err(err) => {
+                    gl.delete_program(program);
+                    Err(err)
+                }
             }
         }
     }
@@ -77,26 +295,47 @@ impl Gradient {
         }
     }
 
-    pub fn paint(&self, gl: &glow::Context, color: Color) {
+    pub fn paint(
+        &self,
+        gl: &glow::Context,
+        color: Color,
+        model: [f32; 3],
+        params: [f32; 4],
+        size: Vec2,
+    ) {
         use glow::HasContext as _;
         unsafe {
             gl.use_program(Some(self.program));
-            match &self.gtype {
-                GradientType::Gradient => gl.uniform_4_f32(
-                    gl.get_uniform_location(self.program, "hue").as_ref(),
-                    color.float_by_name("r"),
-                    color.float_by_name("g"),
-                    color.float_by_name("b"),
-                    1.0,
-                ),
-                GradientType::Slider(_) => gl.uniform_4_f32(
-                    gl.get_uniform_location(self.program, "color").as_ref(),
-                    color.float_by_name("r"),
-                    color.float_by_name("g"),
-                    color.float_by_name("b"),
-                    1.0,
-                ),
-            }
+            gl.uniform_4_f32(
+                gl.get_uniform_location(self.program, "color").as_ref(),
+                color.float_by_name("r"),
+                color.float_by_name("g"),
+                color.float_by_name("b"),
+                1.0,
+            );
+            gl.uniform_4_f32(
+                gl.get_uniform_location(self.program, "hsv").as_ref(),
+                color.float_by_name("h"),
+                color.float_by_name("s"),
+                color.float_by_name("v"),
+                1.0,
+            );
+            gl.uniform_4_f32(
+                gl.get_uniform_location(self.program, "model").as_ref(),
+                model[0],
+                model[1],
+                model[2],
+                1.0,
+            );
+            gl.uniform_4_f32_slice(
+                gl.get_uniform_location(self.program, "params").as_ref(),
+                &params,
+            );
+            gl.uniform_2_f32(
+                gl.get_uniform_location(self.program, "size").as_ref(),
+                size.x,
+                size.y,
+            );
             gl.bind_vertex_array(Some(self.vertex_array));
             gl.draw_arrays(glow::TRIANGLES, 0, 6);
         }
@@ -175,19 +414,146 @@ fn get_shader_sources(gtype: &GradientType) -> (String, String) {
                 return vec4(h, s, cmax, a);
             }
         "#;
+    let hsl_cmyk = r#"
+            vec2 hsv2hsl(float s, float v) {
+                float l = v * (1.0 - s * 0.5);
+                float sl = (l > 0.0 && l < 1.0) ? (v - l) / min(l, 1.0 - l) : 0.0;
+                return vec2(sl, l);
+            }
+
+            vec4 hsl2rgb(float h, float s, float l, float a) {
+                float v = l + s * min(l, 1.0 - l);
+                float sv = v > 0.0 ? 2.0 * (1.0 - l / v) : 0.0;
+                return hsv2rgb(h, sv, v, a);
+            }
+
+            vec4 rgb2cmyk(vec3 rgb) {
+                float k = 1.0 - max(rgb.r, max(rgb.g, rgb.b));
+                if (k >= 1.0) {
+                    return vec4(0.0, 0.0, 0.0, 1.0);
+                }
+                return vec4((vec3(1.0) - rgb - vec3(k)) / (1.0 - k), k);
+            }
+
+            vec4 cmyk2rgb(vec4 cmyk) {
+                return vec4((vec3(1.0) - cmyk.rgb) * (1.0 - cmyk.a), 1.0);
+            }
+        "#;
+
+    // `finish` goes before each shader's `shade` and `main` after it. `main`
+    // dithers the shaded color, unless `params.y` asks for quantized steps,
+    // in which case `q` rounds coordinates and dithering is skipped.
+    let finish = r#"
+            uniform vec4 params;
+
+            float q(float x, float steps) {
+                return params.y > 0.5 ? round(x * steps) / steps : x;
+            }
+
+            // Ordered dithering uses an 8x8 Bayer matrix. Interleaved
+            // gradient noise stands in for a blue noise texture.
+            float dither_noise(vec2 p) {
+                if (params.x < 1.5) {
+                    uvec2 u = uvec2(p) & 7u;
+                    uint xy = u.x ^ u.y;
+                    uint v = ((xy & 1u) << 5) | ((u.y & 1u) << 4) | ((xy & 2u) << 2)
+                        | ((u.y & 2u) << 1) | ((xy & 4u) >> 1) | ((u.y & 4u) >> 2);
+                    return (float(v) + 0.5) / 64.0;
+                }
+                return fract(52.9829189 * fract(dot(p, vec2(0.06711056, 0.00583715))));
+            }
+        "#;
+    let main = r#"
+            void main() {
+                shade();
+                if (params.x > 0.5 && params.y < 0.5) {
+                    float n = dither_noise(floor(gl_FragCoord.xy)) - 0.5;
+                    out_color.rgb += n * out_color.a / 255.0;
+                }
+            }
+        "#;
 
     let fragment_shader_source = match &gtype {
-        GradientType::Gradient => {
-            r#"
-                uniform vec4 hue;
-                in vec2 tex_coord;
-                out vec4 out_color;
-                void main() {
-                    vec4 white = vec4(1.0, 1.0, 1.0, 1.0);
-                    vec4 color = mix(white, hue, tex_coord.x);
-                    out_color = color * tex_coord.y;
-                }
-            "#
+        GradientType::Gradient(shape) => {
+            let var = format!(
+                r#"
+                    const float TAU = 6.28318530718;
+                    const float RING_WIDTH = {RING_WIDTH:?};
+                    const float STRIP_WIDTH = {STRIP_WIDTH:?};
+                    const float STRIP_GAP = {STRIP_GAP:?};
+                    uniform vec4 hsv;
+                    uniform vec4 model;
+                    uniform vec2 size;
+                    in vec2 tex_coord;
+                    out vec4 out_color;
+                "#
+            );
+            let func = match shape {
+                Shape::Plane(plane) => plane_shader(plane),
+                Shape::Triangle => {
+                    r#"
+                    void shade() {
+                        vec2 pt = tex_coord * size;
+                        vec2 center = size * 0.5;
+                        float outer = min(size.x, size.y) * 0.5;
+                        float inner = outer * (1.0 - RING_WIDTH);
+                        vec2 d = pt - center;
+                        float dist = length(d);
+                        if (dist >= inner) {
+                            float h = q(fract(atan(d.y, d.x) / TAU + 1.0), 360.0);
+                            float edge = clamp(min(outer - dist, dist - inner) + 0.5, 0.0, 1.0);
+                            out_color = hsv2rgb(h, 1.0, 1.0, 1.0) * edge;
+                            return;
+                        }
+
+                        float angle = hsv.x * TAU;
+                        vec2 a = center + inner * vec2(cos(angle), sin(angle));
+                        vec2 b = center + inner * vec2(cos(angle + TAU / 3.0), sin(angle + TAU / 3.0));
+                        vec2 c = center + inner * vec2(cos(angle + TAU * 2.0 / 3.0), sin(angle + TAU * 2.0 / 3.0));
+                        vec2 v0 = b - a;
+                        vec2 v1 = c - a;
+                        vec2 v2 = pt - a;
+                        float d00 = dot(v0, v0);
+                        float d01 = dot(v0, v1);
+                        float d11 = dot(v1, v1);
+                        float d20 = dot(v2, v0);
+                        float d21 = dot(v2, v1);
+                        float denom = d00 * d11 - d01 * d01;
+                        float wb = (d11 * d20 - d01 * d21) / denom;
+                        float wc = (d00 * d21 - d01 * d20) / denom;
+                        float wa = 1.0 - wb - wc;
+
+                        float edge = clamp(min(wa, min(wb, wc)) * 1.5 * inner + 0.5, 0.0, 1.0);
+                        float v = max(wa, 0.0) + max(wb, 0.0);
+                        float s = v > 0.0 ? max(wa, 0.0) / v : 0.0;
+                        out_color = hsv2rgb(hsv.x, q(s, 100.0), q(v, 100.0), 1.0) * edge;
+                    }
+                "#
+                .to_string()
+                }
+                Shape::Wheel => {
+                    r#"
+                    void shade() {
+                        vec2 pt = tex_coord * size;
+                        if (pt.x >= size.x - STRIP_WIDTH) {
+                            out_color = hsv2rgb(hsv.x, hsv.y, q(tex_coord.y, 100.0), 1.0);
+                            return;
+                        }
+
+                        float width = size.x - STRIP_WIDTH - STRIP_GAP;
+                        float radius = min(width, size.y) * 0.5;
+                        vec2 d = pt - vec2(width * 0.5, size.y * 0.5);
+                        float dist = length(d);
+                        float h = q(fract(atan(d.y, d.x) / TAU + 1.0), 360.0);
+                        float s = q(min(dist / radius, 1.0), 100.0);
+                        float edge = clamp(radius - dist + 0.5, 0.0, 1.0);
+                        out_color = hsv2rgb(h, s, hsv.z, 1.0) * edge;
+                    }
+                "#
+                .to_string()
+                }
+            };
+            &format!("{hsv2rgb}\n{var}\n{finish}\n{func}\n{main}")
         }
         GradientType::Slider(stype) => {
             let var = r#"
@@ -195,30 +561,43 @@ fn get_shader_sources(gtype: &GradientType) -> (String, String) {
                     in vec2 tex_coord;
                     out vec4 out_color;
                 "#;
-            let func = match stype.as_str() {
-                "r" => "void main() { out_color = vec4(tex_coord.x, color.g, color.b, 1.0); } ",
-                "g" => "void main() { out_color = vec4(color.r, tex_coord.x, color.b, 1.0); } ",
-                "b" => "void main() { out_color = vec4(color.r, color.g, tex_coord.x, 1.0); } ",
-                "h" => {
-                    "void main() {
-                            out_color = hsv2rgb(tex_coord.x, 1.0, 1.0, 1.0);
-                        } "
-                }
+            let body = match stype.as_str() {
+                "r" => "out_color = vec4(x, color.g, color.b, 1.0);",
+                "g" => "out_color = vec4(color.r, x, color.b, 1.0);",
+                "b" => "out_color = vec4(color.r, color.g, x, 1.0);",
+                "h" => "out_color = hsv2rgb(x, 1.0, 1.0, 1.0);",
                 "s" => {
-                    "void main() {
-                            vec4 hsv = rgb2hsv(color.r, color.g, color.b, color.a);
-                            out_color  = hsv2rgb(hsv.r, tex_coord.x, hsv.b, hsv.a);
-                        } "
+                    "vec4 hsv = rgb2hsv(color.r, color.g, color.b, color.a);
+                            out_color  = hsv2rgb(hsv.r, x, hsv.b, hsv.a);"
                 }
                 "v" => {
-                    "void main() {
-                            vec4 hsv = rgb2hsv(color.r, color.g, color.b, color.a);
-                            out_color  = hsv2rgb(hsv.r, hsv.g, tex_coord.x, hsv.a);
-                        } "
+                    "vec4 hsv = rgb2hsv(color.r, color.g, color.b, color.a);
+                            out_color  = hsv2rgb(hsv.r, hsv.g, x, hsv.a);"
                 }
+                "sl" => {
+                    "vec4 hsv = rgb2hsv(color.r, color.g, color.b, color.a);
+                            vec2 hsl = hsv2hsl(hsv.g, hsv.b);
+                            out_color = hsl2rgb(hsv.r, x, hsl.y, 1.0);"
+                }
+                "l" => {
+                    "vec4 hsv = rgb2hsv(color.r, color.g, color.b, color.a);
+                            vec2 hsl = hsv2hsl(hsv.g, hsv.b);
+                            out_color = hsl2rgb(hsv.r, hsl.x, x, 1.0);"
+                }
+                "c" => "vec4 cmyk = rgb2cmyk(color.rgb); cmyk.x = x; out_color = cmyk2rgb(cmyk);",
+                "m" => "vec4 cmyk = rgb2cmyk(color.rgb); cmyk.y = x; out_color = cmyk2rgb(cmyk);",
+                "y" => "vec4 cmyk = rgb2cmyk(color.rgb); cmyk.z = x; out_color = cmyk2rgb(cmyk);",
+                "k" => "vec4 cmyk = rgb2cmyk(color.rgb); cmyk.w = x; out_color = cmyk2rgb(cmyk);",
                 _ => "",
             };
-            &format!("{hsv2rgb}\n{rgb2hsv}\n{var}\n{func}")
+            let max = max_by_name(stype) as f32;
+            let func = format!(
+                "void shade() {{
+                            float x = q(tex_coord.x, {max:?});
+                            {body}
+                        }} "
+            );
+            &format!("{hsv2rgb}\n{rgb2hsv}\n{hsl_cmyk}\n{var}\n{finish}\n{func}\n{main}")
         }
     };
     (
@@ -226,3 +605,91 @@ fn get_shader_sources(gtype: &GradientType) -> (String, String) {
         format!("{shader_version}\n{fragment_shader_source}"),
     )
 }
+
+// Fills a copy of the current color's model coordinates with the texture
+// coordinates along the plane's axes, or the strip's channel on the strip.
+fn plane_shader(plane: &Plane) -> String {
+    let maxes = plane.model.maxes().map(|max| max as f32);
+    let to_rgb = match plane.model {
+        Model::Rgb => "return m;",
+        Model::Hsv => "return hsv2rgb(m.x, m.y, m.z, 1.0).rgb;",
+        Model::Hsl => {
+            r#"
+                        float v = m.z + m.y * min(m.z, 1.0 - m.z);
+                        float s = v > 0.0 ? 2.0 * (1.0 - m.z / v) : 0.0;
+                        return hsv2rgb(m.x, s, v, 1.0).rgb;
+                    "#
+        }
+        Model::Oklch => {
+            r#"
+                        float chroma = m.y * OKLCH_MAX_CHROMA;
+                        float a = chroma * cos(m.z * TAU);
+                        float b = chroma * sin(m.z * TAU);
+                        float l = pow(m.x + 0.3963377774 * a + 0.2158037573 * b, 3.0);
+                        float mm = pow(m.x - 0.1055613458 * a - 0.0638541728 * b, 3.0);
+                        float s = pow(m.x - 0.0894841775 * a - 1.2914855480 * b, 3.0);
+                        vec3 linear = vec3(
+                            4.0767416621 * l - 3.3077115913 * mm + 0.2309699292 * s,
+                            -1.2684380046 * l + 2.6097574011 * mm - 0.3413193965 * s,
+                            -0.0041960863 * l - 0.7034186147 * mm + 1.7076147010 * s
+                        );
+                        vec3 low = linear * 12.92;
+                        vec3 high = 1.055 * pow(max(linear, vec3(0.0)), vec3(1.0 / 2.4)) - 0.055;
+                        return mix(high, low, vec3(lessThanEqual(linear, vec3(0.0031308))));
+                    "#
+        }
+    };
+    format!(
+        r#"
+                    const float OKLCH_MAX_CHROMA = {OKLCH_MAX_CHROMA:?};
+                    const vec3 OUT_OF_GAMUT = vec3(0.15);
+                    const int X = {x};
+                    const int Y = {y};
+                    const int Z = {z};
+                    const float MAX_X = {max_x:?};
+                    const float MAX_Y = {max_y:?};
+                    const float MAX_Z = {max_z:?};
+                    const bool STRIP = {strip};
+
+                    vec3 to_rgb(vec3 m) {{
+                        {to_rgb}
+                    }}
+
+                    vec4 plane_color(vec3 m) {{
+                        vec3 rgb = to_rgb(m);
+                        if (any(lessThan(rgb, vec3(-0.001))) || any(greaterThan(rgb, vec3(1.001)))) {{
+                            return vec4(OUT_OF_GAMUT, 1.0);
+                        }}
+                        return vec4(clamp(rgb, 0.0, 1.0), 1.0);
+                    }}
+
+                    void shade() {{
+                        vec3 m = model.xyz;
+                        vec2 pt = tex_coord * size;
+                        float width = size.x;
+                        if (STRIP) {{
+                            if (pt.x >= size.x - STRIP_WIDTH) {{
+                                m[Z] = q(tex_coord.y, MAX_Z);
+                                out_color = plane_color(m);
+                                return;
+                            }}
+                            width = size.x - STRIP_WIDTH - STRIP_GAP;
+                            if (pt.x > width) {{
+                                out_color = vec4(0.0);
+                                return;
+                            }}
+                        }}
+                        m[X] = q(pt.x / width, MAX_X);
+                        m[Y] = q(tex_coord.y, MAX_Y);
+                        out_color = plane_color(m);
+                    }}
+                "#,
+        x = plane.x,
+        y = plane.y,
+        z = plane.z(),
+        max_x = maxes[plane.x],
+        max_y = maxes[plane.y],
+        max_z = maxes[plane.z()],
+        strip = plane.strip,
+    )
+}
@@ -0,0 +1,407 @@
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::JoinHandle,
+};
+
+use serde_json::Value;
+
+use crate::color::Color;
+
+pub const DEFAULT_KEYWORD: &str = "general:col.active_border";
+// Where hidden windows go, special workspaces are only shown when toggled.
+const HIDDEN_WORKSPACE: &str = "special:waycolor";
+
+// Hyprland's request socket, which takes the same commands as hyprctl.
+#[derive(Debug, Clone)]
+pub struct Hyprland {
+    socket: PathBuf,
+}
+
+impl Hyprland {
+    // Newer Hyprland keeps its sockets under $XDG_RUNTIME_DIR, older
+    // versions under /tmp.
+    pub fn from_env() -> Option<Self> {
+        let signature = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+        let runtime = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+        [runtime, Some(PathBuf::from("/tmp"))]
+            .into_iter()
+            .flatten()
+            .map(|dir| dir.join("hypr").join(&signature).join(".socket.sock"))
+            .find(|path| path.exists())
+            .map(|socket| Self { socket })
+    }
+
+    pub fn request(&self, command: &str) -> Result<String, String> {
+        let mut stream = UnixStream::connect(&self.socket)
+            .map_err(|err| format!("Failed to connect to Hyprland: {err}"))?;
+        stream
+            .write_all(command.as_bytes())
+            .map_err(|err| err.to_string())?;
+        let mut reply = String::new();
+        stream
+            .read_to_string(&mut reply)
+            .map_err(|err| err.to_string())?;
+        Ok(reply)
+    }
+
+    // The option's current value in a form `keyword` accepts back.
+    pub fn option(&self, name: &str) -> Result<String, String> {
+        let reply = self.request(&format!("j/getoption {name}"))?;
+        let missing = || format!("No option {name}: {}", reply.trim());
+        let option = parse(&reply).map_err(|_| missing())?;
+        if let Some(value) = option["custom"].as_str() {
+            // Gradients come back as "aarrggbb aarrggbb 45deg".
+            let tokens: Vec<String> = value
+                .split_whitespace()
+                .map(|token| {
+                    match token.len() == 8 && token.chars().all(|c| c.is_ascii_hexdigit()) {
+                        true => format!("0x{token}"),
+                        false => token.to_string(),
+                    }
+                })
+                .collect();
+            return Ok(tokens.join(" "));
+        }
+        let number = |value: &Value| value.as_number().map(|n| n.to_string());
+        option["str"]
+            .as_str()
+            .map(String::from)
+            .or_else(|| number(&option["int"]))
+            .or_else(|| number(&option["float"]))
+            .ok_or_else(missing)
+    }
+
+    // The output name grim takes for the focused monitor.
+    pub fn focused_output(&self) -> Result<String, String> {
+        let monitors = parse(&self.request("j/monitors")?)?;
+        focused(&monitors)?["name"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| String::from("Monitor has no name"))
+    }
+
+    // Hyprland ignores minimize requests, so hiding moves our window to a
+    // special workspace and showing brings it back to the active one.
+    pub fn hide_window(&self) -> Result<(), String> {
+        let window = format!("pid:{}", std::process::id());
+        self.dispatch(&format!(
+            "dispatch movetoworkspacesilent {HIDDEN_WORKSPACE},{window}"
+        ))
+    }
+
+    pub fn show_window(&self) -> Result<(), String> {
+        let reply = parse(&self.request("j/activeworkspace")?)?;
+        let workspace = reply["id"].as_i64().ok_or("No active workspace")?;
+        let window = format!("pid:{}", std::process::id());
+        self.dispatch(&format!(
+            "[[BATCH]]dispatch movetoworkspacesilent {workspace},{window};\
+             dispatch focuswindow {window}"
+        ))
+    }
+
+    // New windows are tiled, so the compact popup is floated and centered
+    // on the cursor once it's mapped. `window` is a Hyprland window
+    // selector.
+    pub fn float_at_cursor(&self, window: &str, [width, height]: [f32; 2]) -> Result<(), String> {
+        let cursor = parse(&self.request("j/cursorpos")?)?;
+        let (x, y) = cursor["x"]
+            .as_f64()
+            .zip(cursor["y"].as_f64())
+            .ok_or("No cursor position")?;
+        let (x, y) = (x as f32 - width / 2.0, y as f32 - height / 2.0);
+        self.dispatch(&format!(
+            "[[BATCH]]dispatch setfloating {window};\
+             dispatch resizewindowpixel exact {width:.0} {height:.0},{window};\
+             dispatch movewindowpixel exact {x:.0} {y:.0},{window}"
+        ))
+    }
+
+    // Every command in a batch answers "ok" when it worked, the answers
+    // are separated by blank lines.
+    fn dispatch(&self, command: &str) -> Result<(), String> {
+        let reply = self.request(command)?;
+        let errors: Vec<&str> = reply
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && *line != "ok")
+            .collect();
+        match errors.as_slice() {
+            [] if reply.trim().is_empty() => Err(String::from("No reply from Hyprland")),
+            [] => Ok(()),
+            _ => Err(errors.join("\n")),
+        }
+    }
+
+    pub fn set_keyword(&self, name: &str, value: &str) -> Result<(), String> {
+        let reply = self.request(&format!("keyword {name} {value}"))?;
+        match reply.trim() {
+            "ok" => Ok(()),
+            error => Err(error.to_string()),
+        }
+    }
+}
+
+// Applies colors to a keyword as they change and puts the original value
+// back on cancel. Requests go through a thread that only sends the newest
+// color, so dragging never waits on the socket.
+pub struct Preview {
+    pub keyword: String,
+    original: String,
+    last: Option<String>,
+    sender: Sender<String>,
+    errors: Receiver<String>,
+    worker: JoinHandle<()>,
+    hyprland: Hyprland,
+}
+
+impl Preview {
+    pub fn start(hyprland: Hyprland, keyword: &str) -> Result<Self, String> {
+        let original = hyprland.option(keyword)?;
+        let (sender, receiver) = mpsc::channel::<String>();
+        let (errors_sender, errors) = mpsc::channel();
+        let client = hyprland.clone();
+        let name = keyword.to_string();
+        let worker = std::thread::spawn(move || {
+            while let Ok(mut value) = receiver.recv() {
+                while let Ok(newer) = receiver.try_recv() {
+                    value = newer;
+                }
+                if let Err(err) = client.set_keyword(&name, &value) {
+                    let _ = errors_sender.send(err);
+                }
+            }
+        });
+        Ok(Self {
+            keyword: keyword.to_string(),
+            original,
+            last: None,
+            sender,
+            errors,
+            worker,
+            hyprland,
+        })
+    }
+
+    pub fn update(&mut self, color: &Color) {
+        let value = format!("rgb({})", color.hex.trim_start_matches('#'));
+        if self.last.as_ref() != Some(&value) {
+            let _ = self.sender.send(value.clone());
+            self.last = Some(value);
+        }
+    }
+
+    // The latest failure of the worker, if any since the last call.
+    pub fn take_error(&self) -> Option<String> {
+        self.errors.try_iter().last()
+    }
+
+    // Restores the original value once the worker has sent whatever was
+    // still queued, so a late preview can't overwrite it.
+    pub fn cancel(self) -> Result<(), String> {
+        drop(self.sender);
+        let _ = self.worker.join();
+        self.hyprland.set_keyword(&self.keyword, &self.original)
+    }
+}
+
+fn parse(reply: &str) -> Result<Value, String> {
+    serde_json::from_str(reply)
+        .map_err(|err| format!("Unexpected reply from Hyprland ({err}): {}", reply.trim()))
+}
+
+fn focused(monitors: &Value) -> Result<&Value, String> {
+    monitors
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|monitor| monitor["focused"] == true)
+        .ok_or_else(|| String::from("No focused monitor"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        os::unix::net::UnixListener,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // A socket in its own directory that answers each request through
+    // `reply` and records what it was asked.
+    struct FakeHyprland {
+        dir: PathBuf,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl FakeHyprland {
+        fn start(name: &str, reply: impl Fn(&str) -> String + Send + 'static) -> Self {
+            let dir = std::env::temp_dir().join(format!("waycolor-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let listener = UnixListener::bind(dir.join(".socket.sock")).unwrap();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+            std::thread::spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    let mut buffer = [0; 4096];
+                    let len = stream.read(&mut buffer).unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buffer[..len]).to_string();
+                    let answer = reply(&request);
+                    recorded.lock().unwrap().push(request);
+                    let _ = stream.write_all(answer.as_bytes());
+                }
+            });
+            Self { dir, requests }
+        }
+
+        fn client(&self) -> Hyprland {
+            Hyprland {
+                socket: self.dir.join(".socket.sock"),
+            }
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl Drop for FakeHyprland {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn option_reads_every_kind_of_value() {
+        let hyprland = FakeHyprland::start("option", |request| {
+            match request.trim_start_matches("j/getoption ") {
+                "gradient" => r#"{"option": "gradient", "custom": "ff33ccee 00ff99ee 45deg"}"#,
+                "int" => r#"{"option": "int", "int": 2, "set": true}"#,
+                "str" => r#"{"option":"str","str":"say \"hi\"","set":true}"#,
+                "float" => "{\n\t\"option\" : \"float\",\n\t\"float\" : 1.500000\n}",
+                _ => "no such option",
+            }
+            .to_string()
+        });
+        let client = hyprland.client();
+        assert_eq!(
+            client.option("gradient").unwrap(),
+            "0xff33ccee 0x00ff99ee 45deg"
+        );
+        assert_eq!(client.option("int").unwrap(), "2");
+        assert_eq!(client.option("str").unwrap(), r#"say "hi""#);
+        assert_eq!(client.option("float").unwrap(), "1.5");
+        assert!(client.option("missing").is_err());
+    }
+
+    #[test]
+    fn preview_sends_only_the_newest_color_and_restores_on_cancel() {
+        // The first keyword request is held until more colors are queued
+        // behind it.
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+        let wait_release = Mutex::new(wait_release);
+        let hyprland = FakeHyprland::start("preview", move |request| {
+            if request.starts_with("j/getoption") {
+                return String::from(r#"{"option": "border", "int": 7}"#);
+            }
+            if request.contains("FF0000") {
+                let _ = started.send(());
+                let _ = wait_release.lock().unwrap().recv_timeout(TIMEOUT);
+            }
+            String::from("ok")
+        });
+        let mut preview = Preview::start(hyprland.client(), "border").unwrap();
+        preview.update(&Color::from_rgb(255, 0, 0));
+        wait_started.recv_timeout(TIMEOUT).unwrap();
+        preview.update(&Color::from_rgb(0, 255, 0));
+        preview.update(&Color::from_rgb(0, 0, 255));
+        preview.update(&Color::from_rgb(0, 0, 255));
+        release.send(()).unwrap();
+        preview.cancel().unwrap();
+        assert_eq!(
+            hyprland.requests(),
+            [
+                "j/getoption border",
+                "keyword border rgb(FF0000)",
+                "keyword border rgb(0000FF)",
+                "keyword border 7",
+            ]
+        );
+    }
+
+    #[test]
+    fn windows_are_hidden_on_a_special_workspace() {
+        let hyprland = FakeHyprland::start("visibility", |request| {
+            match request {
+                "j/activeworkspace" => r#"{"id": 3, "name": "3", "monitorID": 0}"#,
+                "j/cursorpos" => r#"{"x": 500, "y": 300}"#,
+                _ if request.contains("missing") => "Nothing to move",
+                _ if request.contains("broken") => "ok\n\ninvalid token",
+                _ if request.contains("unanswered") => "",
+                _ if request.starts_with("[[BATCH]]") => "ok\n\nok",
+                _ => "ok",
+            }
+            .to_string()
+        });
+        let client = hyprland.client();
+        client.hide_window().unwrap();
+        client.show_window().unwrap();
+        let window = format!("pid:{}", std::process::id());
+        assert_eq!(
+            hyprland.requests(),
+            [
+                format!("dispatch movetoworkspacesilent special:waycolor,{window}"),
+                String::from("j/activeworkspace"),
+                format!(
+                    "[[BATCH]]dispatch movetoworkspacesilent 3,{window};\
+                     dispatch focuswindow {window}"
+                ),
+            ]
+        );
+        assert_eq!(
+            client.dispatch("dispatch missing"),
+            Err(String::from("Nothing to move"))
+        );
+        client
+            .float_at_cursor("title:^(Popup)$", [200.0, 100.0])
+            .unwrap();
+        assert_eq!(
+            hyprland.requests()[4..],
+            [
+                String::from("j/cursorpos"),
+                String::from(
+                    "[[BATCH]]dispatch setfloating title:^(Popup)$;\
+                     dispatch resizewindowpixel exact 200 100,title:^(Popup)$;\
+                     dispatch movewindowpixel exact 400 250,title:^(Popup)$"
+                ),
+            ]
+        );
+        // "ok" in an error is kept, a batch fails on any answer but "ok".
+        assert_eq!(
+            client.dispatch("[[BATCH]]dispatch a;dispatch broken"),
+            Err(String::from("invalid token"))
+        );
+        assert!(client.dispatch("dispatch unanswered").is_err());
+    }
+
+    #[test]
+    fn focused_monitor_is_found_by_its_flag() {
+        let json = r#"[
+            {"id": 0, "name": "eDP-1", "activeWorkspace": {"id": 1, "name": "1"}, "focused": false},
+            {"id": 1, "name": "DP-1", "activeWorkspace": {"id": 2, "name": "2"}, "focused": true}
+        ]"#;
+        let monitors = parse(json).unwrap();
+        assert_eq!(focused(&monitors).unwrap()["name"], "DP-1");
+        assert!(focused(&parse("[]").unwrap()).is_err());
+        assert!(parse("not json").is_err());
+    }
+}
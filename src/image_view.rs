@@ -0,0 +1,138 @@
+use egui::{
+    Color32, ColorImage, CursorIcon, Pos2, Rect, Sense, Stroke, TextureHandle, Vec2,
+};
+
+use crate::{
+    capture,
+    color::Color,
+    sample::{sample, Area, Sample, Sampling},
+};
+
+// A loaded image that can be panned by dragging and zoomed by scrolling.
+// Clicking samples around the pointer like the loupe does, shift-dragging
+// samples the dragged rectangle.
+pub struct ImageView {
+    pub name: String,
+    image: ColorImage,
+    texture: TextureHandle,
+    // Screen points per image pixel, fitted to the view on first show.
+    zoom: Option<f32>,
+    // The image position shown at the center of the view.
+    center: Vec2,
+    drag_start: Option<[usize; 2]>,
+    hovered: Option<([usize; 2], Sample)>,
+}
+
+impl ImageView {
+    pub fn new(ctx: &egui::Context, name: String, image: ColorImage) -> Self {
+        let texture = capture::load_texture(ctx, &name, &image);
+        let center = Vec2::new(image.size[0] as f32, image.size[1] as f32) / 2.0;
+        Self {
+            name,
+            image,
+            texture,
+            zoom: None,
+            center,
+            drag_start: None,
+            hovered: None,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, sampling: &Sampling) -> Option<Color> {
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), Sense::click_and_drag());
+        let image_size = Vec2::new(self.image.size[0] as f32, self.image.size[1] as f32);
+        let zoom = *self
+            .zoom
+            .get_or_insert_with(|| (rect.size() / image_size).min_elem().min(1.0));
+
+        if response.drag_started() && ui.input(|i| i.modifiers.shift) {
+            let start = response.interact_pointer_pos().unwrap_or(rect.center());
+            self.drag_start = self.pixel_at(rect, start);
+        }
+        if response.dragged() && self.drag_start.is_none() {
+            self.center -= response.drag_delta() / zoom;
+        }
+        if let Some(pos) = response.hover_pos() {
+            let scroll = ui.input(|i| i.raw_scroll_delta.y);
+            if scroll != 0.0 {
+                // Zoom around the pointer so the pixel under it stays put.
+                let factor = if scroll > 0.0 { 1.25 } else { 0.8 };
+                let new_zoom = (zoom * factor).clamp(0.05, 64.0);
+                let at = self.image_pos(rect, pos);
+                self.center = at - (pos - rect.center()) / new_zoom;
+                self.zoom = Some(new_zoom);
+            }
+        }
+        let zoom = self.zoom.unwrap_or(zoom);
+        let origin = rect.center() - self.center * zoom;
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+        painter.image(
+            self.texture.id(),
+            Rect::from_min_size(origin, image_size * zoom),
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+            Color32::WHITE,
+        );
+
+        self.hovered = None;
+        let selection = self.drag_start;
+        let selecting = selection.is_some();
+        if selecting && response.drag_stopped() {
+            self.drag_start = None;
+        }
+        let pos = response.interact_pointer_pos().or(response.hover_pos())?;
+        let area = match selection {
+            // A dragged rectangle may end outside the image, so it's clamped.
+            Some(start) => Area::new(&self.image, start, self.clamped_pixel(rect, pos)),
+            None => sampling.area(&self.image, self.pixel_at(rect, pos)?),
+        };
+        let pixel = self.clamped_pixel(rect, pos);
+        let sampled = sample(&self.image, area, sampling.reduce);
+
+        let min = Vec2::new(area.min[0] as f32, area.min[1] as f32);
+        let max = Vec2::new(area.max[0] as f32 + 1.0, area.max[1] as f32 + 1.0);
+        let outline = Rect::from_min_max(origin + min * zoom, origin + max * zoom);
+        painter.rect_stroke(
+            outline,
+            0.0,
+            Stroke::new(1.5, sampled.color.inv().to_color32()),
+        );
+        if !response.dragged() || selecting {
+            ui.ctx().set_cursor_icon(CursorIcon::Crosshair);
+        }
+
+        let picked = ((selecting && response.drag_stopped()) || response.clicked())
+            .then(|| sampled.color.clone());
+        self.hovered = Some((pixel, sampled));
+        picked
+    }
+
+    pub fn image(&self) -> &ColorImage {
+        &self.image
+    }
+
+    // What's under the pointer, for the status line.
+    pub fn hovered(&self) -> Option<&([usize; 2], Sample)> {
+        self.hovered.as_ref()
+    }
+
+    fn image_pos(&self, rect: Rect, pos: Pos2) -> Vec2 {
+        self.center + (pos - rect.center()) / self.zoom.unwrap_or(1.0)
+    }
+
+    fn pixel_at(&self, rect: Rect, pos: Pos2) -> Option<[usize; 2]> {
+        let p = self.image_pos(rect, pos);
+        let [width, height] = self.image.size;
+        (p.x >= 0.0 && p.y >= 0.0 && p.x < width as f32 && p.y < height as f32)
+            .then_some([p.x as usize, p.y as usize])
+    }
+
+    fn clamped_pixel(&self, rect: Rect, pos: Pos2) -> [usize; 2] {
+        let p = self.image_pos(rect, pos);
+        let [width, height] = self.image.size;
+        [
+            (p.x.max(0.0) as usize).min(width - 1),
+            (p.y.max(0.0) as usize).min(height - 1),
+        ]
+    }
+}
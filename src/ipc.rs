@@ -0,0 +1,329 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+use crate::color::Color;
+
+// The error a pick is answered with when the user backs out of it.
+pub const CANCELLED: &str = "Cancelled";
+
+// How long `send` waits on an instance that stopped answering. A pick waits
+// for the user, so it gets longer.
+const TIMEOUT: Duration = Duration::from_secs(5);
+const PICK_TIMEOUT: Duration = Duration::from_secs(300);
+
+// What a running instance can be asked over its socket, one command per
+// connection as a line of text. Replies are "ok <value>" or
+// "error <message>".
+#[derive(Debug, Clone)]
+pub enum Command {
+    SetColor(Color),
+    GetColor,
+    Pick,
+    Show,
+    Hide,
+    Toggle,
+    Copy,
+    Compact,
+    Enable(Service),
+    Quit,
+}
+
+// What other programs can reach us through besides the socket. Each is
+// off until asked for, with `--<name>` on the command line or `enable`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Service {
+    PortalBackend,
+    Dbus,
+    Tray,
+}
+
+impl Service {
+    pub const ALL: [Service; 3] = [Service::PortalBackend, Service::Dbus, Service::Tray];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Service::PortalBackend => "portal-backend",
+            Service::Dbus => "dbus",
+            Service::Tray => "tray",
+        }
+    }
+
+    pub fn from_flag(arg: &str) -> Option<Self> {
+        let name = arg.strip_prefix("--")?;
+        Self::ALL.into_iter().find(|service| service.name() == name)
+    }
+}
+
+impl Command {
+    pub const USAGE: &'static str = "set-color <#rrggbb> | get-color | pick | show | hide | \
+         toggle | copy | compact | enable <portal-backend|dbus|tray> | quit";
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = match (words.next(), words.next()) {
+            (Some("set-color"), Some(hex)) => Command::SetColor(parse_color(hex)?),
+            (Some("get-color"), None) => Command::GetColor,
+            (Some("pick"), None) => Command::Pick,
+            (Some("show"), None) => Command::Show,
+            (Some("hide"), None) => Command::Hide,
+            (Some("toggle"), None) => Command::Toggle,
+            (Some("copy"), None) => Command::Copy,
+            (Some("compact"), None) => Command::Compact,
+            (Some("enable"), Some(name)) => Service::ALL
+                .into_iter()
+                .find(|service| service.name() == name)
+                .map(Command::Enable)
+                .ok_or_else(|| format!("Unknown service, expected {}", Self::USAGE))?,
+            (Some("quit"), None) => Command::Quit,
+            _ => return Err(format!("Unknown command, expected {}", Self::USAGE)),
+        };
+        match words.next() {
+            Some(_) => Err(format!("Too many arguments, expected {}", Self::USAGE)),
+            None => Ok(command),
+        }
+    }
+}
+
+// Accepts hex with or without the `#`, in either case.
+pub fn parse_color(text: &str) -> Result<Color, String> {
+    let hex = format!("#{}", text.trim().trim_start_matches('#').to_uppercase());
+    Color::from_hex(hex).ok_or_else(|| String::from("Expected a color like #rrggbb"))
+}
+
+// A command waiting for the app to handle it. Replying can happen later,
+// `pick` only answers once the user has picked.
+pub struct Request {
+    pub command: Command,
+    reply: Sender<Result<String, String>>,
+}
+
+impl Request {
+    pub fn new(command: Command) -> (Self, Receiver<Result<String, String>>) {
+        let (reply, receiver) = mpsc::channel();
+        (Self { command, reply }, receiver)
+    }
+
+    // For commands given on the command line that started the app, which
+    // have nobody to reply to.
+    pub fn local(command: Command) -> Self {
+        Self::new(command).0
+    }
+
+    pub fn reply(&self, result: Result<String, String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+pub fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("waycolor.sock"),
+        None => std::env::temp_dir().join(format!(
+            "waycolor-{}.sock",
+            std::env::var("USER").unwrap_or_default()
+        )),
+    }
+}
+
+// Sends a command to the running instance. The outer error means there is
+// none, or a `TimedOut` one that it didn't answer.
+pub fn send(command: &str) -> io::Result<Result<String, String>> {
+    send_to(&socket_path(), command)
+}
+
+fn send_to(path: &Path, command: &str) -> io::Result<Result<String, String>> {
+    let mut stream = UnixStream::connect(path)?;
+    let timeout = match command.split_whitespace().next() {
+        Some("pick") => PICK_TIMEOUT,
+        _ => TIMEOUT,
+    };
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut line = String::new();
+    writeln!(stream, "{command}")
+        .and_then(|()| BufReader::new(stream).read_line(&mut line))
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::WouldBlock => io::Error::new(io::ErrorKind::TimedOut, "no answer"),
+            _ => err,
+        })?;
+    let line = line.trim_end_matches('\n');
+    Ok(match line.split_once(' ').unwrap_or((line, "")) {
+        ("ok", value) => Ok(value.to_string()),
+        (_, error) => Err(error.to_string()),
+    })
+}
+
+// Claims the socket. A socket nobody answers on is left over from a crash
+// and is replaced.
+pub fn bind() -> io::Result<UnixListener> {
+    bind_at(&socket_path())
+}
+
+fn bind_at(path: &Path) -> io::Result<UnixListener> {
+    match UnixListener::bind(path) {
+        Err(err)
+            if err.kind() == io::ErrorKind::AddrInUse && UnixStream::connect(path).is_err() =>
+        {
+            std::fs::remove_file(path)?;
+            UnixListener::bind(path)
+        }
+        result => result,
+    }
+}
+
+pub fn remove_socket() {
+    let _ = std::fs::remove_file(socket_path());
+}
+
+// Accepts connections on a thread, handing each command to the app and
+// waking it up. Each connection waits for its reply on its own thread.
+pub fn serve(listener: UnixListener, ctx: egui::Context, requests: Sender<Request>) {
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let ctx = ctx.clone();
+            let requests = requests.clone();
+            std::thread::spawn(move || handle(stream, &ctx, &requests));
+        }
+    });
+}
+
+fn handle(stream: UnixStream, ctx: &egui::Context, requests: &Sender<Request>) {
+    let mut line = String::new();
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    if BufReader::new(stream).read_line(&mut line).is_err() {
+        return;
+    }
+    let result = match Command::parse(&line) {
+        Ok(command) => {
+            let (request, receiver) = Request::new(command);
+            let _ = requests.send(request);
+            ctx.request_repaint();
+            receiver
+                .recv()
+                .unwrap_or_else(|_| Err(String::from("WayColor is shutting down")))
+        }
+        Err(error) => Err(error),
+    };
+    let _ = match result {
+        Ok(value) => writeln!(writer, "ok {value}"),
+        Err(error) => writeln!(writer, "error {error}"),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    // Answers like the app would, except that picks are dropped unanswered
+    // as when the app closes.
+    fn fake_app(requests: Receiver<Request>) {
+        std::thread::spawn(move || {
+            let mut color = String::from("#161621");
+            for request in requests {
+                let reply = match &request.command {
+                    Command::GetColor => Ok(color.clone()),
+                    Command::SetColor(new) => {
+                        color.clone_from(&new.hex);
+                        Ok(color.clone())
+                    }
+                    Command::Show => Ok(String::new()),
+                    Command::Pick => continue,
+                    _ => Err(String::from("Unexpected command")),
+                };
+                request.reply(reply);
+            }
+        });
+    }
+
+    #[test]
+    fn socket_round_trip() {
+        let dir = std::env::temp_dir().join(format!("waycolor-ipc-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("waycolor.sock");
+        let send = |command: &str| send_to(&path, command);
+        assert!(send("get-color").is_err());
+
+        // A socket left behind by a crash is replaced, a live one isn't.
+        drop(UnixListener::bind(&path).unwrap());
+        let listener = bind_at(&path).unwrap();
+        assert_eq!(bind_at(&path).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+        let (sender, requests) = mpsc::channel();
+        fake_app(requests);
+        serve(listener, egui::Context::default(), sender);
+
+        assert_eq!(send("get-color").unwrap(), Ok(String::from("#161621")));
+        assert_eq!(
+            send("set-color aabbcc").unwrap(),
+            Ok(String::from("#AABBCC"))
+        );
+        assert_eq!(send("get-color").unwrap(), Ok(String::from("#AABBCC")));
+        assert_eq!(send("show").unwrap(), Ok(String::new()));
+        assert_eq!(
+            send("set-color nope").unwrap(),
+            Err(String::from("Expected a color like #rrggbb"))
+        );
+        assert!(send("paint")
+            .unwrap()
+            .unwrap_err()
+            .starts_with("Unknown command"));
+        assert_eq!(
+            send("pick").unwrap(),
+            Err(String::from("WayColor is shutting down"))
+        );
+
+        // Replies are a single line of "ok <value>" or "error <message>".
+        let mut stream = UnixStream::connect(&path).unwrap();
+        writeln!(stream, "set-color #010203").unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        assert_eq!(reply, "ok #010203\n");
+        let mut stream = UnixStream::connect(&path).unwrap();
+        writeln!(stream, "set-color #010203 #040506").unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        assert!(reply.starts_with("error Too many arguments"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(send("get-color").is_err());
+    }
+
+    #[test]
+    fn an_instance_that_never_answers_times_out() {
+        let dir = std::env::temp_dir().join(format!("waycolor-stuck-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("waycolor.sock");
+        // Accepted, but never read from or answered.
+        let listener = bind_at(&path).unwrap();
+        let error = send_to(&path, "get-color").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        drop(listener);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn services_are_named_by_flag_and_enable() {
+        assert_eq!(Service::from_flag("--tray"), Some(Service::Tray));
+        assert_eq!(
+            Service::from_flag("--portal-backend"),
+            Some(Service::PortalBackend)
+        );
+        assert_eq!(Service::from_flag("dbus"), None);
+        assert!(matches!(
+            Command::parse("enable dbus\n"),
+            Ok(Command::Enable(Service::Dbus))
+        ));
+        assert!(matches!(Command::parse("compact"), Ok(Command::Compact)));
+        assert!(Command::parse("enable").is_err());
+        assert!(Command::parse("enable mpris").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use egui::{
+    Align2, Color32, ColorImage, CursorIcon, FontId, Key, Pos2, Rect, Sense, Stroke, TextureHandle,
+    Vec2,
+};
+
+use crate::{
+    capture,
+    color::Color,
+    sample::{sample, Area, Sample, Sampling},
+};
+
+pub enum LoupeEvent {
+    Pick(Color),
+    Cancel,
+}
+
+// A frozen screenshot stretched over the whole window, with a magnified
+// pixel grid following the pointer. Scrolling changes the magnification,
+// [ and ] the sampled area and Tab how it's reduced to one color. Dragging
+// samples the dragged rectangle instead.
+pub struct Loupe {
+    pub sampling: Sampling,
+    drag_start: Option<[usize; 2]>,
+    image: ColorImage,
+    texture: TextureHandle,
+    zoom: f32,
+    size: f32,
+    min_grid_zoom: f32,
+}
+
+impl Loupe {
+    pub fn new(ctx: &egui::Context, image: ColorImage, sampling: Sampling) -> Self {
+        let texture = capture::load_texture(ctx, "screenshot", &image);
+        Self {
+            sampling,
+            drag_start: None,
+            image,
+            texture,
+            zoom: 12.0,
+            size: 165.0,
+            min_grid_zoom: 8.0,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<LoupeEvent> {
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), Sense::click_and_drag());
+        let uv = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+        ui.painter()
+            .image(self.texture.id(), rect, uv, Color32::WHITE);
+
+        if ui.input(|i| i.key_pressed(Key::Escape)) || response.secondary_clicked() {
+            return Some(LoupeEvent::Cancel);
+        }
+        let pos = response.hover_pos()?;
+        let scroll = ui.input(|i| i.raw_scroll_delta.y);
+        if scroll != 0.0 {
+            let factor = if scroll > 0.0 { 1.25 } else { 0.8 };
+            self.zoom = (self.zoom * factor).clamp(4.0, 40.0);
+        }
+        ui.input(|i| {
+            if i.key_pressed(Key::OpenBracket) {
+                self.sampling.shrink();
+            }
+            if i.key_pressed(Key::CloseBracket) {
+                self.sampling.grow();
+            }
+            if i.key_pressed(Key::Tab) {
+                self.sampling.reduce = self.sampling.reduce.next();
+            }
+        });
+
+        let pixel = self.pixel_at(rect, pos);
+        if response.drag_started() {
+            let start = response.interact_pointer_pos().unwrap_or(pos);
+            self.drag_start = Some(self.pixel_at(rect, start));
+        }
+        let area = match self.drag_start {
+            Some(start) => Area::new(&self.image, start, pixel),
+            None => self.sampling.area(&self.image, pixel),
+        };
+        let sampled = sample(&self.image, area, self.sampling.reduce);
+        if response.drag_stopped() {
+            self.drag_start = None;
+            return Some(LoupeEvent::Pick(sampled.color));
+        }
+        if response.clicked() {
+            return Some(LoupeEvent::Pick(sampled.color));
+        }
+        ui.ctx().set_cursor_icon(CursorIcon::Crosshair);
+        if area.size() != [1, 1] {
+            let stroke = Stroke::new(1.0, sampled.color.inv().to_color32());
+            ui.painter()
+                .rect_stroke(self.screen_rect(rect, area), 0.0, stroke);
+        }
+        self.draw_loupe(ui, rect, pos, pixel, area, &sampled);
+        None
+    }
+
+    fn screen_rect(&self, rect: Rect, area: Area) -> Rect {
+        let scale = rect.size() / Vec2::new(self.image.size[0] as f32, self.image.size[1] as f32);
+        let min = Vec2::new(area.min[0] as f32, area.min[1] as f32);
+        let max = Vec2::new(area.max[0] as f32 + 1.0, area.max[1] as f32 + 1.0);
+        Rect::from_min_max(rect.min + min * scale, rect.min + max * scale)
+    }
+
+    // The screenshot is stretched to the window, which matches it pixel for
+    // pixel when fullscreen on a single output.
+    fn pixel_at(&self, rect: Rect, pos: Pos2) -> [usize; 2] {
+        let [width, height] = self.image.size;
+        let t = (pos - rect.min) / rect.size();
+        [
+            ((t.x * width as f32) as usize).min(width - 1),
+            ((t.y * height as f32) as usize).min(height - 1),
+        ]
+    }
+
+    fn draw_loupe(
+        &self,
+        ui: &egui::Ui,
+        rect: Rect,
+        pos: Pos2,
+        pixel: [usize; 2],
+        area: Area,
+        sampled: &Sample,
+    ) {
+        let color = &sampled.color;
+        let painter = ui.painter();
+        let size = Vec2::splat(self.size);
+        // Keep the loupe beside the pointer, flipping sides near the edges.
+        let offset = Vec2::splat(24.0);
+        let mut min = pos + offset;
+        if min.x + size.x > rect.max.x {
+            min.x = pos.x - offset.x - size.x;
+        }
+        if min.y + size.y + 76.0 > rect.max.y {
+            min.y = pos.y - offset.y - size.y - 76.0;
+        }
+        let loupe = Rect::from_min_size(min, size);
+
+        let image_size = Vec2::new(self.image.size[0] as f32, self.image.size[1] as f32);
+        let center = Vec2::new(pixel[0] as f32 + 0.5, pixel[1] as f32 + 0.5);
+        let half = size / self.zoom * 0.5;
+        let uv = Rect::from_min_max(
+            ((center - half) / image_size).to_pos2(),
+            ((center + half) / image_size).to_pos2(),
+        );
+        painter.image(self.texture.id(), loupe, uv, Color32::WHITE);
+
+        if self.zoom >= self.min_grid_zoom {
+            let grid = Stroke::new(1.0, Color32::from_black_alpha(60));
+            let count = (half.x.ceil() + 1.0) as i32;
+            for i in -count..=count {
+                let d = (i as f32 - 0.5) * self.zoom;
+                let x = loupe.center().x + d;
+                let y = loupe.center().y + d;
+                if loupe.x_range().contains(x) {
+                    painter.vline(x, loupe.y_range(), grid);
+                }
+                if loupe.y_range().contains(y) {
+                    painter.hline(loupe.x_range(), y, grid);
+                }
+            }
+        }
+        // The sampled area, positioned relative to the pixel at the center.
+        let offset = |p: usize, c: usize| (p as f32 - c as f32 - 0.5) * self.zoom;
+        let target = Rect::from_min_max(
+            loupe.center()
+                + Vec2::new(offset(area.min[0], pixel[0]), offset(area.min[1], pixel[1])),
+            loupe.center()
+                + Vec2::new(
+                    offset(area.max[0] + 1, pixel[0]),
+                    offset(area.max[1] + 1, pixel[1]),
+                ),
+        )
+        .intersect(loupe);
+        painter.rect_stroke(target, 0.0, Stroke::new(2.0, color.inv().to_color32()));
+        painter.rect_stroke(
+            loupe,
+            2.0,
+            Stroke::new(2.0, ui.visuals().strong_text_color()),
+        );
+
+        let [width, height] = area.size();
+        let text = format!(
+            "{}  ({}, {})\nRGB {} {} {}\nHSV {} {} {}\n{} {}\u{d7}{}  \u{b1}{:.1}",
+            color.hex,
+            pixel[0],
+            pixel[1],
+            color.r,
+            color.g,
+            color.b,
+            color.h,
+            color.s,
+            color.v,
+            self.sampling.reduce.name(),
+            width,
+            height,
+            sampled.spread
+        );
+        let info = Rect::from_min_size(
+            loupe.left_bottom() + Vec2::new(0.0, 6.0),
+            Vec2::new(size.x, 70.0),
+        );
+        painter.rect_filled(info, 2.0, ui.visuals().extreme_bg_color);
+        let swatch = Rect::from_min_size(info.min + Vec2::splat(6.0), Vec2::splat(14.0));
+        painter.rect_filled(swatch, 2.0, color.to_color32());
+        painter.text(
+            info.min + Vec2::new(26.0, 5.0),
+            Align2::LEFT_TOP,
+            text,
+            FontId::monospace(11.0),
+            ui.visuals().text_color(),
+        );
+    }
+}
@@ -1,16 +1,159 @@
 mod app;
+mod capture;
 mod color;
+mod dbus;
+mod expression;
 mod gradient;
+mod hyprland;
+mod image_view;
+mod ipc;
+mod loupe;
+mod mesh;
+mod palette;
+mod paths;
+mod portal;
+mod portal_backend;
+mod sample;
+mod scheme;
+mod settings;
+mod shape;
+mod templates;
 mod theme;
+mod tray;
+mod wallpaper;
+mod wgpu_gradient;
+
+use std::os::unix::net::UnixListener;
 
 fn main() -> eframe::Result {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // `waycolor msg <command>` only talks to a running instance.
+    if args.first().is_some_and(|arg| arg == "msg") {
+        match ipc::send(&args[1..].join(" ")) {
+            Ok(reply) => exit_with(reply),
+            Err(err) if err.kind() == std::io::ErrorKind::TimedOut => not_answering(),
+            Err(err) => {
+                eprintln!("WayColor isn't running ({err}).");
+                std::process::exit(1);
+            }
+        }
+    }
+    // `--portal-backend`, `--dbus` and `--tray` turn on the matching
+    // service, which is off otherwise. The portal backend flag is also how
+    // D-Bus activation starts us for xdg-desktop-portal, see
+    // `portal_backend`.
+    let mut services = Vec::new();
+    let mut args = args;
+    args.retain(|arg| match ipc::Service::from_flag(arg) {
+        Some(service) => {
+            services.push(service);
+            false
+        }
+        None => true,
+    });
+    let backend = services.contains(&ipc::Service::PortalBackend);
+    // Any launch is forwarded to the running instance if there is one, and
+    // otherwise becomes the first commands of this one.
+    let mut commands: Vec<String> = services
+        .iter()
+        .map(|service| format!("enable {}", service.name()))
+        .collect();
+    if !args.is_empty() {
+        commands.push(args.join(" "));
+    } else if services.is_empty() {
+        commands.push(String::from("show"));
+    }
+    match ipc::send(&commands[0]) {
+        Ok(mut reply) => {
+            for command in &commands[1..] {
+                if reply.is_err() {
+                    break;
+                }
+                reply = ipc::send(command).unwrap_or_else(|err| Err(err.to_string()));
+            }
+            exit_with(reply);
+        }
+        // Starting a second window next to a stuck one would only add to it.
+        Err(err) if err.kind() == std::io::ErrorKind::TimedOut => not_answering(),
+        Err(_) => {}
+    }
+    let mut startup: Vec<ipc::Command> = services.into_iter().map(ipc::Command::Enable).collect();
+    if backend && args.is_empty() {
+        startup.push(ipc::Command::Hide);
+    } else if !args.is_empty() {
+        match ipc::Command::parse(&args.join(" ")) {
+            // A popup launched from a hotkey comes without the main window.
+            Ok(command @ ipc::Command::Compact) => startup.extend([ipc::Command::Hide, command]),
+            Ok(command) => startup.push(command),
+            Err(error) => {
+                eprintln!("{error}");
+                std::process::exit(2);
+            }
+        }
+    }
+    let listener = ipc::bind()
+        .map_err(|err| eprintln!("Failed to listen on {:?}: {err}", ipc::socket_path()))
+        .ok();
+
+    let renderer = match std::env::var("WAYCOLOR_RENDERER").as_deref() {
+        Ok("wgpu") => eframe::Renderer::Wgpu,
+        _ => eframe::Renderer::Glow,
+    };
+    let result = run(renderer, &listener, &startup).or_else(|err| match renderer {
+        eframe::Renderer::Glow => {
+            eprintln!("Failed to start with OpenGL ({err}), falling back to wgpu.");
+            run(eframe::Renderer::Wgpu, &listener, &startup)
+        }
+        eframe::Renderer::Wgpu => Err(err),
+    });
+    // Only the instance that owns the socket removes it, one that failed to
+    // bind would take away the running instance's.
+    if listener.is_some() {
+        ipc::remove_socket();
+    }
+    result
+}
+
+fn exit_with(reply: Result<String, String>) -> ! {
+    match reply {
+        Ok(value) => {
+            if !value.is_empty() {
+                println!("{value}");
+            }
+            std::process::exit(0);
+        }
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn not_answering() -> ! {
+    eprintln!("WayColor is running but didn't answer.");
+    std::process::exit(1);
+}
+
+fn run(
+    renderer: eframe::Renderer,
+    listener: &Option<UnixListener>,
+    startup: &[ipc::Command],
+) -> eframe::Result {
+    // The listener is cloned so it survives a failed first attempt.
+    let listener = listener.as_ref().and_then(|l| l.try_clone().ok());
+    let startup = startup.to_vec();
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_app_id("WayColor")
             .with_inner_size([415.0, 525.0])
-            .with_resizable(false),
-        multisampling: 8,
-        renderer: eframe::Renderer::Glow,
+            .with_min_inner_size([315.0, 415.0]),
+        // Software wgpu adapters rarely support MSAA.
+        multisampling: if renderer == eframe::Renderer::Glow {
+            8
+        } else {
+            1
+        },
+        renderer,
         ..Default::default()
     };
 
@@ -19,7 +162,7 @@ fn main() -> eframe::Result {
         options,
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::new(app::App::new(cc)))
+            Ok(Box::new(app::App::new(cc, listener, startup)))
         }),
     )
 }
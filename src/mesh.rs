@@ -0,0 +1,237 @@
+use std::f32::consts::TAU;
+
+use egui::{epaint::Mesh, Color32, Painter, Pos2, Rect, Vec2};
+
+use crate::{
+    color::{hsl_to_hsv, hsv_to_rgb01, Color, Model},
+    gradient::{GradientPainter, GradientType, RenderOptions},
+    shape::{barycentric, Plane, Shape, RING_WIDTH, STRIP_GAP, STRIP_WIDTH},
+};
+
+pub struct MeshPainter;
+
+impl GradientPainter for MeshPainter {
+    fn name(&self) -> &'static str {
+        "Mesh"
+    }
+
+    // Vertex colors are interpolated between grid points, so neither
+    // dithering nor quantized steps would survive. Both are left to the
+    // shaders.
+    fn paint(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        gtype: &GradientType,
+        color: Color,
+        _options: RenderOptions,
+    ) {
+        paint(painter, rect, gtype, &color);
+    }
+}
+
+// CPU fallback for the gradient shaders. Every gradient is piecewise linear
+// or bilinear in its coordinates, so a grid of vertex-colored quads gets
+// close enough to the per-pixel result.
+fn paint(painter: &Painter, rect: Rect, gtype: &GradientType, color: &Color) {
+    let (columns, rows) = match gtype {
+        GradientType::Gradient(Shape::Plane(Plane::SV)) => (32, 32),
+        GradientType::Gradient(_) => (128, 128),
+        GradientType::Slider(_) => (60, 1),
+    };
+
+    let mut mesh = Mesh::default();
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let x = column as f32 / columns as f32;
+            let y = row as f32 / rows as f32;
+            let pos = Pos2::new(
+                rect.min.x + rect.width() * x,
+                rect.max.y - rect.height() * y,
+            );
+            mesh.colored_vertex(pos, sample(gtype, color, rect.size(), x, y));
+        }
+    }
+    for row in 0..rows {
+        for column in 0..columns {
+            let i = row * (columns + 1) + column;
+            let above = i + columns + 1;
+            mesh.add_triangle(i, i + 1, above + 1);
+            mesh.add_triangle(i, above + 1, above);
+        }
+    }
+    painter.add(egui::Shape::mesh(mesh));
+}
+
+// Mirrors the fragment shaders in `gradient::get_shader_sources`, with x and
+// y matching `tex_coord`.
+fn sample(gtype: &GradientType, color: &Color, size: Vec2, x: f32, y: f32) -> Color32 {
+    let r = color.float_by_name("r");
+    let g = color.float_by_name("g");
+    let b = color.float_by_name("b");
+    let h = color.float_by_name("h");
+    let s = color.float_by_name("s");
+    let v = color.float_by_name("v");
+    let pt = Vec2::new(x, y) * size;
+    let ([r, g, b], alpha) = match gtype {
+        GradientType::Gradient(Shape::Plane(plane)) => {
+            let mut m = plane.model.coords(color);
+            let width = if plane.strip {
+                size.x - STRIP_WIDTH - STRIP_GAP
+            } else {
+                size.x
+            };
+            if plane.strip && pt.x >= size.x - STRIP_WIDTH {
+                m[plane.z()] = y;
+                (plane_rgb(plane.model, m), 1.0)
+            } else if pt.x > width {
+                ([0.0; 3], 0.0)
+            } else {
+                m[plane.x] = pt.x / width;
+                m[plane.y] = y;
+                (plane_rgb(plane.model, m), 1.0)
+            }
+        }
+        GradientType::Gradient(Shape::Triangle) => {
+            let center = size * 0.5;
+            let outer = size.x.min(size.y) * 0.5;
+            let inner = outer * (1.0 - RING_WIDTH);
+            let d = pt - center;
+            let dist = d.length();
+            if dist >= inner {
+                let edge = ((outer - dist).min(dist - inner) + 0.5).clamp(0.0, 1.0);
+                (hsv_to_rgb01(hue_angle(d), 1.0, 1.0), edge)
+            } else {
+                let corner = |i: f32| {
+                    let angle = h * TAU + i * TAU / 3.0;
+                    (center + Vec2::new(angle.cos(), angle.sin()) * inner).to_pos2()
+                };
+                let [wa, wb, wc] =
+                    barycentric([corner(0.0), corner(1.0), corner(2.0)], pt.to_pos2());
+                let edge = (wa.min(wb).min(wc) * 1.5 * inner + 0.5).clamp(0.0, 1.0);
+                let hue = hsv_to_rgb01(h, 1.0, 1.0).map(|c| c * wa.max(0.0) + wb.max(0.0));
+                (hue, edge)
+            }
+        }
+        GradientType::Gradient(Shape::Wheel) => {
+            if pt.x >= size.x - STRIP_WIDTH {
+                (hsv_to_rgb01(h, s, y), 1.0)
+            } else {
+                let width = size.x - STRIP_WIDTH - STRIP_GAP;
+                let radius = width.min(size.y) * 0.5;
+                let d = pt - Vec2::new(width * 0.5, size.y * 0.5);
+                let dist = d.length();
+                let edge = (radius - dist + 0.5).clamp(0.0, 1.0);
+                let saturation = (dist / radius).min(1.0);
+                (hsv_to_rgb01(hue_angle(d), saturation, v), edge)
+            }
+        }
+        GradientType::Slider(stype) => {
+            let rgb = match stype.as_str() {
+                "r" => [x, g, b],
+                "g" => [r, x, b],
+                "b" => [r, g, x],
+                "h" => hsv_to_rgb01(x, 1.0, 1.0),
+                "s" => hsv_to_rgb01(h, x, v),
+                "v" => hsv_to_rgb01(h, s, x),
+                "sl" => hsl2rgb(h, x, color.float_by_name("l")),
+                "l" => hsl2rgb(h, color.float_by_name("sl"), x),
+                "c" | "m" | "y" | "k" => {
+                    let [c, m, y, k] = ["c", "m", "y", "k"].map(|n| {
+                        if n == stype {
+                            x
+                        } else {
+                            color.float_by_name(n)
+                        }
+                    });
+                    [c, m, y].map(|c| (1.0 - c) * (1.0 - k))
+                }
+                _ => [0.0, 0.0, 0.0],
+            };
+            (rgb, 1.0)
+        }
+    };
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * alpha * 255.0).round() as u8;
+    Color32::from_rgba_premultiplied(channel(r), channel(g), channel(b), channel(1.0))
+}
+
+// Colors outside the sRGB gamut are drawn in a flat dark gray, like the
+// shaders do.
+fn plane_rgb(model: Model, m: [f32; 3]) -> [f32; 3] {
+    let rgb = model.to_rgb(m);
+    if rgb.iter().any(|c| !(-0.001..=1.001).contains(c)) {
+        [0.15; 3]
+    } else {
+        rgb
+    }
+}
+
+fn hsl2rgb(h: f32, s: f32, l: f32) -> [f32; 3] {
+    let (s, v) = hsl_to_hsv(s, l);
+    hsv_to_rgb01(h, s, v)
+}
+
+fn hue_angle(d: Vec2) -> f32 {
+    (d.y.atan2(d.x) / TAU).rem_euclid(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The fallback draws the same colors the pickers produce, at least where
+    // the grid has its vertices.
+    #[test]
+    fn plane_corners_match_model_colors() {
+        let size = Vec2::new(200.0, 150.0);
+        let color = Color::from_hsv(200, 60, 70);
+        for model in Model::ALL {
+            for (x, y) in [(0, 1), (1, 2), (2, 0)] {
+                let plane = Plane {
+                    model,
+                    x,
+                    y,
+                    strip: false,
+                };
+                let gtype = GradientType::Gradient(Shape::Plane(plane));
+                for corner in [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]] {
+                    let mut coords = model.coords(&color);
+                    coords[x] = corner[0];
+                    coords[y] = corner[1];
+                    if model
+                        .to_rgb(coords)
+                        .iter()
+                        .any(|c| !(0.0..=1.0).contains(c))
+                    {
+                        continue;
+                    }
+                    let expected = model.color(coords).to_color32();
+                    let drawn = sample(&gtype, &color, size, corner[0], corner[1]);
+                    for (a, b) in expected.to_array().into_iter().zip(drawn.to_array()) {
+                        assert!(
+                            a.abs_diff(b) <= 1,
+                            "{} {corner:?}: {expected:?} != {drawn:?}",
+                            plane.name()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hue_slider_matches_colors() {
+        let gtype = GradientType::Slider(String::from("h"));
+        let color = Color::default();
+        for hue in [0, 60, 120, 180, 240, 300, 360] {
+            let drawn = sample(
+                &gtype,
+                &color,
+                Vec2::new(100.0, 10.0),
+                hue as f32 / 360.0,
+                0.5,
+            );
+            assert_eq!(drawn, Color::from_hsv(hue, 100, 100).to_color32(), "{hue}");
+        }
+    }
+}
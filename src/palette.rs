@@ -0,0 +1,288 @@
+use egui::ColorImage;
+
+use crate::color::{oklab_to_rgb, rgb_to_oklab, Color};
+
+// Pixels beyond this are skipped evenly, which keeps extraction quick on
+// large images without visibly changing the result.
+const MAX_SAMPLES: usize = 20_000;
+const KMEANS_ITERATIONS: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Method {
+    MedianCut,
+    #[default]
+    KMeans,
+}
+
+impl Method {
+    pub const ALL: [Method; 2] = [Method::MedianCut, Method::KMeans];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Method::MedianCut => "Median cut",
+            Method::KMeans => "k-means",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Swatch {
+    pub color: Color,
+    // Fraction of the sampled pixels closest to this swatch.
+    pub share: f32,
+}
+
+// Dominant colors of an image, most common first. Both methods work in
+// OKLab so clusters follow perceived rather than numeric differences.
+// k-means starts from the median cut result, which keeps it deterministic.
+pub fn extract(image: &ColorImage, count: usize, method: Method) -> Vec<Swatch> {
+    let points = lab_points(image);
+    if points.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let mut clusters = median_cut(&points, count);
+    if method == Method::KMeans {
+        let centers = clusters.iter().map(|c| mean(c)).collect();
+        clusters = kmeans(&points, centers);
+    }
+    let mut swatches: Vec<Swatch> = clusters
+        .iter()
+        .filter(|c| !c.is_empty())
+        .map(|c| Swatch {
+            color: lab_color(mean(c)),
+            share: c.len() as f32 / points.len() as f32,
+        })
+        .collect();
+    swatches.sort_by(|a, b| b.share.total_cmp(&a.share));
+    swatches
+}
+
+fn lab_color(lab: [f32; 3]) -> Color {
+    let [r, g, b] = oklab_to_rgb(lab).map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u16);
+    Color::from_rgb(r, g, b)
+}
+
+fn lab_points(image: &ColorImage) -> Vec<[f32; 3]> {
+    let step = image.pixels.len().div_ceil(MAX_SAMPLES).max(1);
+    image
+        .pixels
+        .iter()
+        .step_by(step)
+        .filter(|p| p.a() >= 128)
+        .map(|p| rgb_to_oklab([p.r(), p.g(), p.b()].map(|c| c as f32 / 255.0)))
+        .collect()
+}
+
+// Splits the box with the largest spread times population at the median
+// of its widest axis until there are `count` boxes.
+fn median_cut(points: &[[f32; 3]], count: usize) -> Vec<Vec<[f32; 3]>> {
+    let mut boxes = vec![points.to_vec()];
+    while boxes.len() < count {
+        let Some((index, axis, _)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (axis, range) = widest_axis(b);
+                (i, axis, range * b.len() as f32)
+            })
+            .filter(|(_, _, score)| *score > 0.0)
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+        else {
+            break;
+        };
+        let mut split = boxes.swap_remove(index);
+        split.sort_by(|a, b| a[axis].total_cmp(&b[axis]));
+        // Split between distinct values so identical colors stay together.
+        let median = split[split.len() / 2][axis];
+        let mut at = split.partition_point(|p| p[axis] < median);
+        if at == 0 {
+            at = split.partition_point(|p| p[axis] <= median);
+        }
+        let upper = split.split_off(at);
+        boxes.push(split);
+        boxes.push(upper);
+    }
+    boxes
+}
+
+fn widest_axis(points: &[[f32; 3]]) -> (usize, f32) {
+    (0..3)
+        .map(|axis| {
+            let (min, max) = points.iter().fold((f32::MAX, f32::MIN), |(min, max), p| {
+                (min.min(p[axis]), max.max(p[axis]))
+            });
+            (axis, max - min)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0, 0.0))
+}
+
+fn kmeans(points: &[[f32; 3]], mut centers: Vec<[f32; 3]>) -> Vec<Vec<[f32; 3]>> {
+    let mut clusters = Vec::new();
+    for _ in 0..KMEANS_ITERATIONS {
+        clusters = vec![Vec::new(); centers.len()];
+        for p in points {
+            let nearest = (0..centers.len())
+                .min_by(|&a, &b| distance(p, &centers[a]).total_cmp(&distance(p, &centers[b])))
+                .unwrap_or(0);
+            clusters[nearest].push(*p);
+        }
+        let mut moved = 0.0f32;
+        for (center, cluster) in centers.iter_mut().zip(&clusters) {
+            if !cluster.is_empty() {
+                let new = mean(cluster);
+                moved = moved.max(distance(center, &new));
+                *center = new;
+            }
+        }
+        if moved < 1e-6 {
+            break;
+        }
+    }
+    clusters
+}
+
+// Squared OKLab distance.
+fn distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum()
+}
+
+fn mean(points: &[[f32; 3]]) -> [f32; 3] {
+    let mut sum = [0.0; 3];
+    for p in points {
+        for i in 0..3 {
+            sum[i] += p[i];
+        }
+    }
+    sum.map(|s| s / points.len().max(1) as f32)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    Gpl,
+    Hex,
+    Css,
+    Json,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Gpl, Format::Hex, Format::Css, Format::Json];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Gpl => "GIMP palette",
+            Format::Hex => "Hex list",
+            Format::Css => "CSS variables",
+            Format::Json => "JSON",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Gpl => "gpl",
+            Format::Hex => "hex",
+            Format::Css => "css",
+            Format::Json => "json",
+        }
+    }
+
+    pub fn export(&self, name: &str, swatches: &[Swatch]) -> String {
+        let percent = |s: &Swatch| s.share * 100.0;
+        match self {
+            Format::Gpl => {
+                let mut text = format!("GIMP Palette\nName: {name}\nColumns: 8\n#\n");
+                for s in swatches {
+                    let c = &s.color;
+                    text += &format!(
+                        "{:3} {:3} {:3}\t{} {:.1}%\n",
+                        c.r,
+                        c.g,
+                        c.b,
+                        c.hex,
+                        percent(s)
+                    );
+                }
+                text
+            }
+            Format::Hex => swatches
+                .iter()
+                .map(|s| format!("{}\n", s.color.hex))
+                .collect(),
+            Format::Css => {
+                let mut text = String::from(":root {\n");
+                for (i, s) in swatches.iter().enumerate() {
+                    text += &format!(
+                        "  --color-{}: {}; /* {:.1}% */\n",
+                        i + 1,
+                        s.color.hex,
+                        percent(s)
+                    );
+                }
+                text + "}\n"
+            }
+            Format::Json => {
+                let entries: Vec<String> = swatches
+                    .iter()
+                    .map(|s| {
+                        format!(
+                            "  {{ \"hex\": \"{}\", \"share\": {:.4} }}",
+                            s.color.hex, s.share
+                        )
+                    })
+                    .collect();
+                format!("[\n{}\n]\n", entries.join(",\n"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+
+    use super::*;
+
+    // Three quarters red, one quarter blue, and some transparent pixels
+    // that shouldn't count.
+    fn image() -> ColorImage {
+        let mut pixels = vec![Color32::RED; 60];
+        pixels.extend([Color32::BLUE; 20]);
+        pixels.extend([Color32::TRANSPARENT; 20]);
+        ColorImage {
+            size: [10, 10],
+            pixels,
+        }
+    }
+
+    #[test]
+    fn extract_finds_colors_by_share() {
+        for method in Method::ALL {
+            let swatches = extract(&image(), 4, method);
+            assert_eq!(swatches.len(), 2, "{}", method.name());
+            assert_eq!(swatches[0].color.hex, "#FF0000");
+            assert_eq!(swatches[1].color.hex, "#0000FF");
+            assert!((swatches[0].share - 0.75).abs() < 1e-6);
+        }
+        assert!(extract(&image(), 0, Method::KMeans).is_empty());
+        let empty = ColorImage::new([4, 4], Color32::TRANSPARENT);
+        assert!(extract(&empty, 4, Method::MedianCut).is_empty());
+    }
+
+    #[test]
+    fn exports() {
+        let swatches = extract(&image(), 2, Method::MedianCut);
+        let gpl = Format::Gpl.export("test", &swatches);
+        assert!(gpl.starts_with("GIMP Palette\nName: test\n"));
+        assert!(gpl.contains("255   0   0\t#FF0000 75.0%\n"));
+        assert_eq!(Format::Hex.export("test", &swatches), "#FF0000\n#0000FF\n");
+        let css = Format::Css.export("test", &swatches);
+        assert!(css.contains("  --color-2: #0000FF; /* 25.0% */\n"));
+        assert_eq!(
+            Format::Json.export("test", &swatches),
+            "[\n  { \"hex\": \"#FF0000\", \"share\": 0.7500 },\n  \
+             { \"hex\": \"#0000FF\", \"share\": 0.2500 }\n]\n"
+        );
+    }
+}
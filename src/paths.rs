@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+// Paths typed into the app or read from config files. Surrounding
+// whitespace is dropped and a leading `~/` stands for $HOME.
+pub fn expand_home(path: &str) -> PathBuf {
+    let path = path.trim();
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// Where templates.conf and settings.conf live, under `waycolor/`.
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_home_trims_and_expands() {
+        assert_eq!(expand_home("  /tmp/a b \n"), PathBuf::from("/tmp/a b"));
+        assert_eq!(expand_home("a/~/b"), PathBuf::from("a/~/b"));
+        if let Some(home) = std::env::var_os("HOME") {
+            let expected = PathBuf::from(home).join("Pictures/wall.png");
+            assert_eq!(expand_home(" ~/Pictures/wall.png\n"), expected);
+        }
+    }
+}
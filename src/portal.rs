@@ -0,0 +1,137 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use zbus::{
+    blocking::{Connection, Proxy, SignalIterator},
+    zvariant::{OwnedObjectPath, OwnedValue, Value},
+};
+
+static NEXT_TOKEN: AtomicUsize = AtomicUsize::new(0);
+
+// One xdg-desktop-portal request. Portals answer through a `Response`
+// signal on a request object, so we subscribe to it before making the
+// call, using the object path the portal derives from our name and token.
+pub struct Request {
+    connection: Connection,
+    token: String,
+    responses: SignalIterator<'static>,
+}
+
+impl Request {
+    pub fn new() -> Result<Self, String> {
+        let connection = Connection::session().map_err(|err| err.to_string())?;
+        let token = format!(
+            "waycolor{}_{}",
+            std::process::id(),
+            NEXT_TOKEN.fetch_add(1, Ordering::Relaxed)
+        );
+        let sender = connection
+            .unique_name()
+            .ok_or("No unique bus name")?
+            .trim_start_matches(':')
+            .replace('.', "_");
+        let request = Proxy::new(
+            &connection,
+            "org.freedesktop.portal.Desktop",
+            format!("/org/freedesktop/portal/desktop/request/{sender}/{token}"),
+            "org.freedesktop.portal.Request",
+        )
+        .map_err(|err| err.to_string())?;
+        let responses = request
+            .receive_signal("Response")
+            .map_err(|err| err.to_string())?;
+        Ok(Self {
+            connection,
+            token,
+            responses,
+        })
+    }
+
+    // Goes in the call's options as `handle_token`.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn proxy(&self, interface: &'static str) -> Result<Proxy<'static>, String> {
+        Proxy::new(
+            &self.connection,
+            "org.freedesktop.portal.Desktop",
+            "/org/freedesktop/portal/desktop",
+            interface,
+        )
+        .map_err(|err| err.to_string())
+    }
+
+    // Blocks until the user has answered. `None` means they cancelled.
+    pub fn response(mut self) -> Result<Option<HashMap<String, OwnedValue>>, String> {
+        let response = self.responses.next().ok_or("No response from portal")?;
+        let (code, results): (u32, HashMap<String, OwnedValue>) =
+            response.body().map_err(|err| err.to_string())?;
+        Ok((code == 0).then_some(results))
+    }
+}
+
+// Asks the portal's file chooser for an image. `None` means the dialog was
+// dismissed.
+pub fn choose_image() -> Result<Option<PathBuf>, String> {
+    let request = Request::new()?;
+    let filter = ("Images", vec![(0u32, "*.png"), (0, "*.jpg"), (0, "*.jpeg")]);
+    let options = HashMap::from([
+        ("handle_token", Value::from(request.token())),
+        ("filters", Value::from(vec![filter])),
+    ]);
+    let _: OwnedObjectPath = request
+        .proxy("org.freedesktop.portal.FileChooser")?
+        .call("OpenFile", &("", "Open image", options))
+        .map_err(|err| err.to_string())?;
+
+    first_uri(request)
+}
+
+fn first_uri(request: Request) -> Result<Option<PathBuf>, String> {
+    let Some(results) = request.response()? else {
+        return Ok(None);
+    };
+    let uri = results
+        .get("uris")
+        .and_then(|uris| Vec::<String>::try_from(uris.clone()).ok())
+        .and_then(|uris| uris.into_iter().next())
+        .ok_or("Portal returned no URI")?;
+    uri_to_path(&uri)
+        .map(Some)
+        .ok_or_else(|| format!("Unsupported URI: {uri}"))
+}
+
+// Asks the file chooser where to save, suggesting `current_name`.
+pub fn choose_save_path(title: &str, current_name: &str) -> Result<Option<PathBuf>, String> {
+    let request = Request::new()?;
+    let options = HashMap::from([
+        ("handle_token", Value::from(request.token())),
+        ("current_name", Value::from(current_name)),
+    ]);
+    let _: OwnedObjectPath = request
+        .proxy("org.freedesktop.portal.FileChooser")?
+        .call("SaveFile", &("", title, options))
+        .map_err(|err| err.to_string())?;
+    first_uri(request)
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let [first, tail @ ..] = rest {
+        if *first == b'%' && tail.len() >= 2 {
+            let hex = std::str::from_utf8(&tail[..2]).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(*first);
+            rest = tail;
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+};
+
+use zbus::{
+    blocking::{Connection, ConnectionBuilder},
+    dbus_interface,
+    zvariant::{ObjectPath, OwnedValue, Structure, Value},
+};
+
+use crate::{
+    capture, dbus,
+    ipc::{self, Command, Request},
+};
+
+// xdg-desktop-portal finds backends through data/waycolor.portal, but only
+// routes to us where portals.conf says so, see data/portals.conf. It starts
+// us with `--portal-backend` through the matching D-Bus service file when
+// the name isn't taken yet. Without that flag, or `enable portal-backend`
+// on a running instance, the name is never registered.
+pub const NAME: &str = "org.freedesktop.impl.portal.desktop.waycolor";
+const PATH: &str = "/org/freedesktop/portal/desktop";
+
+// Portal response codes.
+const SUCCESS: u32 = 0;
+const CANCELLED: u32 = 1;
+const FAILED: u32 = 2;
+
+static NEXT_SCREENSHOT: AtomicUsize = AtomicUsize::new(0);
+
+type Results = HashMap<&'static str, OwnedValue>;
+
+// The Screenshot implementation portals delegate to. PickColor runs the
+// same flow as the loupe button. Screenshot comes along because portals
+// route whole interfaces, it only uses grim to not end up calling itself
+// and refuses interactive requests, there is no dialog to offer.
+struct Screenshot {
+    requests: Sender<Request>,
+    ctx: egui::Context,
+    errors: Sender<String>,
+}
+
+#[dbus_interface(name = "org.freedesktop.impl.portal.Screenshot")]
+impl Screenshot {
+    async fn screenshot(
+        &self,
+        _handle: ObjectPath<'_>,
+        _app_id: &str,
+        _parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> (u32, Results) {
+        if matches!(options.get("interactive"), Some(Value::Bool(true))) {
+            return (FAILED, HashMap::new());
+        }
+        // $XDG_RUNTIME_DIR is private to the user, unlike /tmp.
+        let dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        let path = dir.join(format!(
+            "waycolor-screenshot-{}-{}.png",
+            std::process::id(),
+            NEXT_SCREENSHOT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let uri = format!("file://{}", path.display());
+        match blocking::unblock(move || capture::save_screenshot(&path)).await {
+            Ok(()) => (SUCCESS, HashMap::from([("uri", Value::from(uri).into())])),
+            Err(error) => {
+                self.report(error);
+                (FAILED, HashMap::new())
+            }
+        }
+    }
+
+    // The color goes back as sRGB components in 0..1, `(ddd)`.
+    async fn pick_color(
+        &self,
+        _handle: ObjectPath<'_>,
+        _app_id: &str,
+        _parent_window: &str,
+        _options: HashMap<&str, Value<'_>>,
+    ) -> (u32, Results) {
+        let color = dbus::request(&self.requests, &self.ctx, Command::Pick)
+            .await
+            .and_then(|hex| ipc::parse_color(&hex));
+        match color {
+            Ok(color) => {
+                let [r, g, b] = [color.r, color.g, color.b].map(|c| c as f64 / 255.0);
+                let color = Value::from(Structure::from((r, g, b)));
+                (SUCCESS, HashMap::from([("color", color.into())]))
+            }
+            Err(error) if error == ipc::CANCELLED => (CANCELLED, HashMap::new()),
+            Err(error) => {
+                self.report(error);
+                (FAILED, HashMap::new())
+            }
+        }
+    }
+
+    #[dbus_interface(property, name = "version")]
+    fn version(&self) -> u32 {
+        2
+    }
+}
+
+impl Screenshot {
+    fn report(&self, error: String) {
+        let _ = self.errors.send(error);
+        self.ctx.request_repaint();
+    }
+}
+
+pub struct Backend {
+    _connection: Connection,
+    errors: Receiver<String>,
+}
+
+impl Backend {
+    pub fn start(ctx: egui::Context, requests: Sender<Request>) -> Result<Self, String> {
+        Self::serve(ConnectionBuilder::session(), ctx, requests)
+    }
+
+    fn serve(
+        builder: zbus::Result<ConnectionBuilder<'static>>,
+        ctx: egui::Context,
+        requests: Sender<Request>,
+    ) -> Result<Self, String> {
+        let (sender, errors) = mpsc::channel();
+        let screenshot = Screenshot {
+            requests,
+            ctx,
+            errors: sender,
+        };
+        let connection = builder
+            .and_then(|builder| builder.serve_at(PATH, screenshot))
+            .and_then(|builder| builder.name(NAME))
+            .and_then(|builder| builder.build())
+            .map_err(|err| err.to_string())?;
+        Ok(Self {
+            _connection: connection,
+            errors,
+        })
+    }
+
+    // Calls are answered on the connection's thread, which leaves failed
+    // captures and picks here.
+    pub fn take_error(&self) -> Option<String> {
+        self.errors.try_iter().last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use zbus::{blocking::Proxy, zvariant::OwnedObjectPath};
+
+    use super::*;
+    use crate::dbus::tests::{fake_app, Bus};
+
+    const INTERFACE: &str = "org.freedesktop.impl.portal.Screenshot";
+
+    type Properties = HashMap<String, OwnedValue>;
+
+    fn call(proxy: &Proxy, method: &str, options: HashMap<&str, Value>) -> (u32, Properties) {
+        let handle =
+            OwnedObjectPath::try_from("/org/freedesktop/portal/desktop/request/1_0/t").unwrap();
+        proxy
+            .call(method, &(handle, "org.example.App", "", options))
+            .unwrap()
+    }
+
+    #[test]
+    fn pick_color_and_screenshot() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon isn't available, skipping");
+            return;
+        };
+        let (sender, requests) = mpsc::channel();
+        fake_app(requests);
+        let builder = ConnectionBuilder::address(bus.address.as_str());
+        let backend = Backend::serve(builder, egui::Context::default(), sender).unwrap();
+
+        let client = bus.connect();
+        let proxy = Proxy::new(&client, NAME, PATH, INTERFACE).unwrap();
+        let (response, results) = call(&proxy, "PickColor", HashMap::new());
+        assert_eq!(response, SUCCESS);
+        let color = Structure::try_from(results["color"].clone()).unwrap();
+        let (r, g, b) = <(f64, f64, f64)>::try_from(color).unwrap();
+        assert_eq!([r, g, b], [0x12, 0x34, 0x56].map(|c| c as f64 / 255.0));
+        // The fake app cancels every pick after the first.
+        let (response, results) = call(&proxy, "PickColor", HashMap::new());
+        assert_eq!(response, CANCELLED);
+        assert!(results.is_empty());
+        assert_eq!(backend.take_error(), None);
+
+        let interactive = HashMap::from([("interactive", Value::from(true))]);
+        assert_eq!(
+            call(&proxy, "Screenshot", interactive),
+            (FAILED, HashMap::new())
+        );
+        // Without grim the capture fails, and the failure is reported.
+        let (response, results) = call(&proxy, "Screenshot", HashMap::new());
+        match response {
+            SUCCESS => assert!(results.contains_key("uri")),
+            _ => assert!(backend.take_error().is_some()),
+        }
+    }
+}
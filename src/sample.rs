@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use egui::ColorImage;
+
+use crate::color::{linear_to_srgb, srgb_to_linear, Color};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Reduce {
+    #[default]
+    Mean,
+    Median,
+    Dominant,
+}
+
+impl Reduce {
+    pub const ALL: [Reduce; 3] = [Reduce::Mean, Reduce::Median, Reduce::Dominant];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Reduce::Mean => "Mean",
+            Reduce::Median => "Median",
+            Reduce::Dominant => "Dominant",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|r| *r == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+// How a click on a screenshot turns pixels into one color. `size` is the
+// side of the square sampled around the pointer and is kept odd so the
+// square stays centered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    pub size: usize,
+    pub reduce: Reduce,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            size: 1,
+            reduce: Reduce::Mean,
+        }
+    }
+}
+
+impl Sampling {
+    pub const SIZES: [usize; 6] = [1, 3, 5, 9, 15, 31];
+
+    pub fn grow(&mut self) {
+        self.size = Self::SIZES
+            .into_iter()
+            .find(|s| *s > self.size)
+            .unwrap_or(self.size);
+    }
+
+    pub fn shrink(&mut self) {
+        self.size = Self::SIZES
+            .into_iter()
+            .rev()
+            .find(|s| *s < self.size)
+            .unwrap_or(self.size);
+    }
+
+    // The square around `center`, clipped to the image.
+    pub fn area(&self, image: &ColorImage, [x, y]: [usize; 2]) -> Area {
+        let r = self.size / 2;
+        Area::new(
+            image,
+            [x.saturating_sub(r), y.saturating_sub(r)],
+            [x + r, y + r],
+        )
+    }
+}
+
+// An inclusive pixel rectangle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub min: [usize; 2],
+    pub max: [usize; 2],
+}
+
+impl Area {
+    pub fn new(image: &ColorImage, a: [usize; 2], b: [usize; 2]) -> Self {
+        let [width, height] = image.size;
+        Self {
+            min: [a[0].min(b[0]), a[1].min(b[1])],
+            max: [
+                a[0].max(b[0]).min(width - 1),
+                a[1].max(b[1]).min(height - 1),
+            ],
+        }
+    }
+
+    pub fn size(&self) -> [usize; 2] {
+        [self.max[0] - self.min[0] + 1, self.max[1] - self.min[1] + 1]
+    }
+}
+
+pub struct Sample {
+    pub color: Color,
+    // Root mean square distance of the pixels from `color`, in 0..255 RGB
+    // units, so 0 means the area was perfectly flat.
+    pub spread: f32,
+}
+
+pub fn sample(image: &ColorImage, area: Area, reduce: Reduce) -> Sample {
+    let pixels: Vec<[u8; 3]> = (area.min[1]..=area.max[1])
+        .flat_map(|y| (area.min[0]..=area.max[0]).map(move |x| [x, y]))
+        .map(|[x, y]| {
+            let p = image.pixels[y * image.size[0] + x];
+            [p.r(), p.g(), p.b()]
+        })
+        .collect();
+    let rgb = match reduce {
+        Reduce::Mean => mean(&pixels),
+        Reduce::Median => median(&pixels),
+        Reduce::Dominant => dominant(&pixels),
+    };
+    let spread = (pixels
+        .iter()
+        .map(|p| {
+            (0..3)
+                .map(|i| (p[i] as f32 - rgb[i] as f32).powi(2))
+                .sum::<f32>()
+        })
+        .sum::<f32>()
+        / pixels.len() as f32)
+        .sqrt();
+    Sample {
+        color: Color::from_rgb(rgb[0] as u16, rgb[1] as u16, rgb[2] as u16),
+        spread,
+    }
+}
+
+// Averaged in linear light, so anti-aliased edges blend the way they were
+// rendered.
+fn mean(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0.0; 3];
+    for p in pixels {
+        for i in 0..3 {
+            sum[i] += srgb_to_linear(p[i] as f32 / 255.0);
+        }
+    }
+    sum.map(|s| (linear_to_srgb(s / pixels.len() as f32) * 255.0).round() as u8)
+}
+
+fn median(pixels: &[[u8; 3]]) -> [u8; 3] {
+    [0, 1, 2].map(|i| {
+        let mut channel: Vec<u8> = pixels.iter().map(|p| p[i]).collect();
+        channel.sort_unstable();
+        channel[channel.len() / 2]
+    })
+}
+
+// The most common color after dropping the low three bits of each channel,
+// so near-identical noise still lands in one bucket. The bucket's pixels
+// are then averaged to get the exact value back.
+fn dominant(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let mut buckets: HashMap<[u8; 3], Vec<[u8; 3]>> = HashMap::new();
+    for p in pixels {
+        buckets.entry(p.map(|c| c >> 3)).or_default().push(*p);
+    }
+    let bucket = buckets
+        .into_values()
+        .max_by_key(|b| b.len())
+        .unwrap_or_default();
+    mean(&bucket)
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+
+    use super::*;
+
+    // A 4x4 image: the left half black, the right half white, with one red
+    // pixel at [3, 0].
+    fn image() -> ColorImage {
+        let mut image = ColorImage::new([4, 4], Color32::BLACK);
+        for y in 0..4 {
+            for x in 2..4 {
+                image.pixels[y * 4 + x] = Color32::WHITE;
+            }
+        }
+        image.pixels[3] = Color32::RED;
+        image
+    }
+
+    #[test]
+    fn sampling_sizes_step_through_the_list() {
+        let mut sampling = Sampling::default();
+        sampling.shrink();
+        assert_eq!(sampling.size, 1);
+        sampling.grow();
+        sampling.grow();
+        assert_eq!(sampling.size, 5);
+        for _ in 0..10 {
+            sampling.grow();
+        }
+        assert_eq!(sampling.size, 31);
+        assert_eq!(Reduce::Dominant.next(), Reduce::Mean);
+    }
+
+    #[test]
+    fn areas_are_clipped_to_the_image() {
+        let image = image();
+        let sampling = Sampling {
+            size: 3,
+            reduce: Reduce::Mean,
+        };
+        let area = sampling.area(&image, [0, 0]);
+        assert_eq!((area.min, area.max), ([0, 0], [1, 1]));
+        let area = sampling.area(&image, [3, 3]);
+        assert_eq!((area.min, area.max), ([2, 2], [3, 3]));
+        let area = Area::new(&image, [3, 1], [1, 9]);
+        assert_eq!((area.min, area.max, area.size()), ([1, 1], [3, 3], [3, 3]));
+    }
+
+    #[test]
+    fn reductions() {
+        let image = image();
+        let all = Area::new(&image, [0, 0], [3, 3]);
+        let flat = sample(&image, Area::new(&image, [0, 0], [1, 1]), Reduce::Mean);
+        assert_eq!(flat.color.hex, "#000000");
+        assert_eq!(flat.spread, 0.0);
+        // Half black and half white averages to mid gray in linear light.
+        let mean = sample(&image, Area::new(&image, [1, 1], [2, 1]), Reduce::Mean);
+        assert_eq!(mean.color.hex, "#BCBCBC");
+        let below_red = Area::new(&image, [0, 1], [3, 3]);
+        assert_eq!(sample(&image, below_red, Reduce::Median).color.hex, "#FFFFFF");
+        assert_eq!(sample(&image, all, Reduce::Dominant).color.hex, "#000000");
+        assert!(sample(&image, all, Reduce::Mean).spread > 0.0);
+    }
+}
@@ -0,0 +1,575 @@
+use crate::{
+    color::{oklch_to_rgb, rgb_to_oklch, Color},
+    palette::Swatch,
+};
+
+// Minimum contrast of every text color against the background, WCAG AA.
+pub const MIN_CONTRAST: f32 = 4.5;
+// Foreground and the brightest text get AAA.
+const STRONG_CONTRAST: f32 = 7.0;
+// Bright black is meant for muted text, so it gets the large text minimum.
+const MUTED_CONTRAST: f32 = 3.0;
+
+// OKLCH hues, in turns, of ANSI red, green, yellow, blue, magenta and cyan.
+const HUES: [f32; 6] = [0.08, 0.39, 0.31, 0.73, 0.91, 0.54];
+// How far a swatch's hue may be from an ANSI hue and still stand in for it.
+const HUE_TOLERANCE: f32 = 0.1;
+
+const ANSI_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+// A terminal color scheme, `colors` in ANSI order: black, red, green,
+// yellow, blue, magenta, cyan and white, then their bright variants.
+#[derive(Debug, Clone)]
+pub struct Scheme {
+    pub background: Color,
+    pub foreground: Color,
+    pub cursor: Color,
+    pub selection_background: Color,
+    pub selection_foreground: Color,
+    pub colors: [Color; 16],
+    // Slots that couldn't reach their contrast target when proposed, even
+    // at full black or white.
+    pub unmet: Vec<Slot>,
+}
+
+// One editable color of a scheme.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slot {
+    Background,
+    Foreground,
+    Cursor,
+    SelectionBackground,
+    SelectionForeground,
+    Ansi(usize),
+}
+
+impl Slot {
+    pub const SPECIAL: [Slot; 5] = [
+        Slot::Background,
+        Slot::Foreground,
+        Slot::Cursor,
+        Slot::SelectionBackground,
+        Slot::SelectionForeground,
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            Slot::Background => String::from("Background"),
+            Slot::Foreground => String::from("Foreground"),
+            Slot::Cursor => String::from("Cursor"),
+            Slot::SelectionBackground => String::from("Selection background"),
+            Slot::SelectionForeground => String::from("Selection foreground"),
+            Slot::Ansi(i) if *i < 8 => format!("color{i} ({})", ANSI_NAMES[*i]),
+            Slot::Ansi(i) => format!("color{i} (bright {})", ANSI_NAMES[i % 8]),
+        }
+    }
+
+    // What the slot's contrast is measured against.
+    pub fn against(&self) -> Slot {
+        match self {
+            Slot::Background => Slot::Foreground,
+            Slot::SelectionForeground => Slot::SelectionBackground,
+            Slot::SelectionBackground => Slot::SelectionForeground,
+            _ => Slot::Background,
+        }
+    }
+}
+
+impl Scheme {
+    // Proposes a scheme from a palette. The ANSI colors borrow the hue and
+    // chroma of the nearest swatch where one is close enough, then their
+    // lightness is pushed away from the background until they contrast
+    // with it. Without a `background` the darkest or lightest swatch is
+    // used, toned down to something that works behind text. A given
+    // background decides by itself whether the scheme is dark.
+    pub fn propose(swatches: &[Swatch], dark: bool, background: Option<&Color>) -> Self {
+        let lch: Vec<[f32; 3]> = swatches.iter().map(|s| oklch(&s.color)).collect();
+        let dark = background.map_or(dark, |color| {
+            Color::from_rgb(255, 255, 255).contrast(color)
+                >= Color::from_rgb(0, 0, 0).contrast(color)
+        });
+        let background = match background {
+            Some(color) => color.clone(),
+            None => {
+                let by_lightness = |a: &&[f32; 3], b: &&[f32; 3]| a[0].total_cmp(&b[0]);
+                let base = if dark {
+                    lch.iter().min_by(by_lightness)
+                } else {
+                    lch.iter().max_by(by_lightness)
+                }
+                .copied()
+                .unwrap_or([0.0, 0.0, 0.0]);
+                let lightness = if dark {
+                    base[0].min(0.25)
+                } else {
+                    base[0].max(0.95)
+                };
+                lch_color([lightness, base[1].min(0.04), base[2]])
+            }
+        };
+        let bg = oklch(&background);
+        let neutral = |lightness: f32| [lightness, bg[1].min(0.015), bg[2]];
+        let toward = |lightness: f32, by: f32| {
+            if dark {
+                lightness + by
+            } else {
+                lightness - by
+            }
+        };
+
+        let average_chroma = match lch.len() {
+            0 => 0.12,
+            n => lch.iter().map(|c| c[1]).sum::<f32>() / n as f32,
+        };
+        let accents = HUES.map(|hue| {
+            let nearest = lch
+                .iter()
+                .filter(|c| c[1] > 0.04)
+                .map(|c| (hue_distance(c[2], hue), c))
+                .filter(|(d, _)| *d <= HUE_TOLERANCE)
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            match nearest {
+                Some((_, c)) => [c[1].clamp(0.08, 0.2), c[2]],
+                None => [average_chroma.clamp(0.08, 0.16), hue],
+            }
+        });
+        let base_lightness = if dark { 0.7 } else { 0.5 };
+        let normal = accents.map(|[c, h]| [base_lightness, c, h]);
+        let bright = accents.map(|[c, h]| [toward(base_lightness, 0.08), c, h]);
+
+        let mut unmet = Vec::new();
+        let mut ensure = |slot: Slot, lch: [f32; 3], against: &Color, target: f32| {
+            let (color, met) = ensure_contrast(lch, against, target);
+            if !met {
+                unmet.push(slot);
+            }
+            color
+        };
+        let mut colors: [Color; 16] = Default::default();
+        for i in 0..6 {
+            colors[i + 1] = ensure(Slot::Ansi(i + 1), normal[i], &background, MIN_CONTRAST);
+            colors[i + 9] = ensure(Slot::Ansi(i + 9), bright[i], &background, MIN_CONTRAST);
+        }
+        // Black and white swap roles between dark and light schemes: one
+        // sits next to the background, the other is text.
+        if dark {
+            colors[0] = lch_color(neutral(bg[0] + 0.06));
+            colors[8] = ensure(Slot::Ansi(8), neutral(bg[0] + 0.3), &background, MUTED_CONTRAST);
+            colors[7] = ensure(Slot::Ansi(7), neutral(0.82), &background, MIN_CONTRAST);
+            colors[15] = ensure(Slot::Ansi(15), neutral(0.95), &background, STRONG_CONTRAST);
+        } else {
+            colors[0] = ensure(Slot::Ansi(0), neutral(0.25), &background, STRONG_CONTRAST);
+            colors[8] = ensure(Slot::Ansi(8), neutral(0.55), &background, MUTED_CONTRAST);
+            colors[7] = lch_color(neutral(bg[0] - 0.1));
+            colors[15] = lch_color(neutral(bg[0] - 0.04));
+        }
+        let foreground = ensure(
+            Slot::Foreground,
+            neutral(toward(bg[0], 0.65).clamp(0.0, 1.0)),
+            &background,
+            STRONG_CONTRAST,
+        );
+        let selection_background = lch_color(neutral(toward(bg[0], 0.2).clamp(0.0, 1.0)));
+        let selection_foreground = ensure(
+            Slot::SelectionForeground,
+            oklch(&foreground),
+            &selection_background,
+            MIN_CONTRAST,
+        );
+        Scheme {
+            cursor: foreground.clone(),
+            selection_foreground,
+            selection_background,
+            background,
+            foreground,
+            colors,
+            unmet,
+        }
+    }
+
+    pub fn get(&self, slot: Slot) -> &Color {
+        match slot {
+            Slot::Background => &self.background,
+            Slot::Foreground => &self.foreground,
+            Slot::Cursor => &self.cursor,
+            Slot::SelectionBackground => &self.selection_background,
+            Slot::SelectionForeground => &self.selection_foreground,
+            Slot::Ansi(i) => &self.colors[i],
+        }
+    }
+
+    pub fn set(&mut self, slot: Slot, color: Color) {
+        self.unmet.retain(|unmet| *unmet != slot);
+        match slot {
+            Slot::Background => self.background = color,
+            Slot::Foreground => self.foreground = color,
+            Slot::Cursor => self.cursor = color,
+            Slot::SelectionBackground => self.selection_background = color,
+            Slot::SelectionForeground => self.selection_foreground = color,
+            Slot::Ansi(i) => self.colors[i] = color,
+        }
+    }
+
+    // Lowest contrast of the text colors against the background, leaving
+    // out muted bright black and whichever of black and white is meant to
+    // blend with the background.
+    pub fn min_contrast(&self) -> f32 {
+        let dark = self.background.luminance() < self.foreground.luminance();
+        let blending: &[usize] = if dark { &[0, 8] } else { &[7, 8, 15] };
+        self.colors
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !blending.contains(i))
+            .map(|(_, c)| c.contrast(&self.background))
+            .fold(self.foreground.contrast(&self.background), f32::min)
+    }
+}
+
+// Moves lightness away from the background, towards whichever of black
+// and white contrasts more with it, until `target` is met. The result is
+// measured after rounding to 8 bits so the guarantee holds for the hex.
+// Also returns whether `target` was met before running out of lightness.
+fn ensure_contrast(
+    [lightness, chroma, hue]: [f32; 3],
+    background: &Color,
+    target: f32,
+) -> (Color, bool) {
+    let white = Color::from_rgb(255, 255, 255);
+    let black = Color::from_rgb(0, 0, 0);
+    let up = white.contrast(background) >= black.contrast(background);
+    let mut lightness = lightness.clamp(0.0, 1.0);
+    loop {
+        let color = lch_color([lightness, chroma, hue]);
+        let limit = if up {
+            lightness >= 1.0
+        } else {
+            lightness <= 0.0
+        };
+        let met = color.contrast(background) >= target;
+        if met || limit {
+            return (color, met);
+        }
+        lightness = if up {
+            (lightness + 0.01).min(1.0)
+        } else {
+            (lightness - 0.01).max(0.0)
+        };
+    }
+}
+
+// Reduces chroma until the color fits in sRGB, keeping lightness and hue.
+fn lch_color([lightness, chroma, hue]: [f32; 3]) -> Color {
+    let in_gamut = |c: f32| {
+        oklch_to_rgb([lightness, c, hue])
+            .iter()
+            .all(|v| (-1e-3..=1.0 + 1e-3).contains(v))
+    };
+    let mut chroma = chroma.max(0.0);
+    if !in_gamut(chroma) {
+        let (mut low, mut high) = (0.0, chroma);
+        for _ in 0..16 {
+            let mid = (low + high) / 2.0;
+            if in_gamut(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        chroma = low;
+    }
+    let [r, g, b] = oklch_to_rgb([lightness, chroma, hue])
+        .map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u16);
+    Color::from_rgb(r, g, b)
+}
+
+fn oklch(color: &Color) -> [f32; 3] {
+    rgb_to_oklch([color.r, color.g, color.b].map(|c| c as f32 / 255.0))
+}
+
+fn hue_distance(a: f32, b: f32) -> f32 {
+    let d = (a - b).rem_euclid(1.0);
+    d.min(1.0 - d)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Terminal {
+    #[default]
+    Kitty,
+    Foot,
+    Alacritty,
+    Wezterm,
+    Xresources,
+    Windows,
+}
+
+impl Terminal {
+    pub const ALL: [Terminal; 6] = [
+        Terminal::Kitty,
+        Terminal::Foot,
+        Terminal::Alacritty,
+        Terminal::Wezterm,
+        Terminal::Xresources,
+        Terminal::Windows,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Terminal::Kitty => "kitty",
+            Terminal::Foot => "foot",
+            Terminal::Alacritty => "Alacritty",
+            Terminal::Wezterm => "WezTerm",
+            Terminal::Xresources => "Xresources",
+            Terminal::Windows => "Windows Terminal",
+        }
+    }
+
+    pub fn file_name(&self, name: &str) -> String {
+        match self {
+            Terminal::Kitty => format!("{name}.conf"),
+            Terminal::Foot => format!("{name}.ini"),
+            Terminal::Alacritty | Terminal::Wezterm => format!("{name}.toml"),
+            Terminal::Xresources => format!("{name}.Xresources"),
+            Terminal::Windows => format!("{name}.json"),
+        }
+    }
+
+    pub fn export(&self, name: &str, scheme: &Scheme) -> String {
+        let hex = |color: &Color| color.hex.clone();
+        let bare = |color: &Color| color.hex.trim_start_matches('#').to_lowercase();
+        let colors = &scheme.colors;
+        let mut text = String::new();
+        match self {
+            Terminal::Kitty => {
+                text += &format!("# {name}\n");
+                text += &format!("foreground {}\n", hex(&scheme.foreground));
+                text += &format!("background {}\n", hex(&scheme.background));
+                text += &format!("cursor {}\n", hex(&scheme.cursor));
+                text += &format!("cursor_text_color {}\n", hex(&scheme.background));
+                text += &format!(
+                    "selection_foreground {}\n",
+                    hex(&scheme.selection_foreground)
+                );
+                text += &format!(
+                    "selection_background {}\n",
+                    hex(&scheme.selection_background)
+                );
+                for (i, color) in colors.iter().enumerate() {
+                    text += &format!("color{i} {}\n", hex(color));
+                }
+            }
+            // foot wants bare hex, and the cursor as "text cursor".
+            Terminal::Foot => {
+                text += &format!("# {name}\n[colors]\n");
+                text += &format!("foreground={}\n", bare(&scheme.foreground));
+                text += &format!("background={}\n", bare(&scheme.background));
+                text += &format!(
+                    "cursor={} {}\n",
+                    bare(&scheme.background),
+                    bare(&scheme.cursor)
+                );
+                text += &format!(
+                    "selection-foreground={}\n",
+                    bare(&scheme.selection_foreground)
+                );
+                text += &format!(
+                    "selection-background={}\n",
+                    bare(&scheme.selection_background)
+                );
+                for (i, color) in colors.iter().enumerate() {
+                    let kind = if i < 8 { "regular" } else { "bright" };
+                    text += &format!("{kind}{}={}\n", i % 8, bare(color));
+                }
+            }
+            Terminal::Alacritty => {
+                text += &format!("# {name}\n");
+                text += &format!(
+                    "[colors.primary]\nbackground = \"{}\"\nforeground = \"{}\"\n\n",
+                    hex(&scheme.background),
+                    hex(&scheme.foreground)
+                );
+                text += &format!(
+                    "[colors.cursor]\ntext = \"{}\"\ncursor = \"{}\"\n\n",
+                    hex(&scheme.background),
+                    hex(&scheme.cursor)
+                );
+                text += &format!(
+                    "[colors.selection]\ntext = \"{}\"\nbackground = \"{}\"\n",
+                    hex(&scheme.selection_foreground),
+                    hex(&scheme.selection_background)
+                );
+                for (table, range) in [("normal", 0..8), ("bright", 8..16)] {
+                    text += &format!("\n[colors.{table}]\n");
+                    for i in range {
+                        text += &format!("{} = \"{}\"\n", ANSI_NAMES[i % 8], hex(&colors[i]));
+                    }
+                }
+            }
+            Terminal::Wezterm => {
+                let list = |range: std::ops::Range<usize>| {
+                    colors[range]
+                        .iter()
+                        .map(|c| format!("\"{}\"", hex(c)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                text += "[colors]\n";
+                text += &format!("foreground = \"{}\"\n", hex(&scheme.foreground));
+                text += &format!("background = \"{}\"\n", hex(&scheme.background));
+                text += &format!("cursor_bg = \"{}\"\n", hex(&scheme.cursor));
+                text += &format!("cursor_fg = \"{}\"\n", hex(&scheme.background));
+                text += &format!("cursor_border = \"{}\"\n", hex(&scheme.cursor));
+                text += &format!("selection_fg = \"{}\"\n", hex(&scheme.selection_foreground));
+                text += &format!("selection_bg = \"{}\"\n", hex(&scheme.selection_background));
+                text += &format!("ansi = [{}]\n", list(0..8));
+                text += &format!("brights = [{}]\n", list(8..16));
+                text += &format!("\n[metadata]\nname = {}\n", toml_string(name));
+            }
+            Terminal::Xresources => {
+                text += &format!("! {name}\n");
+                text += &format!("*.foreground: {}\n", hex(&scheme.foreground));
+                text += &format!("*.background: {}\n", hex(&scheme.background));
+                text += &format!("*.cursorColor: {}\n", hex(&scheme.cursor));
+                for (i, color) in colors.iter().enumerate() {
+                    text += &format!("*.color{i}: {}\n", hex(color));
+                }
+            }
+            // Windows Terminal calls magenta purple.
+            Terminal::Windows => {
+                let mut entries = serde_json::json!({
+                    "name": name,
+                    "background": hex(&scheme.background),
+                    "foreground": hex(&scheme.foreground),
+                    "cursorColor": hex(&scheme.cursor),
+                    "selectionBackground": hex(&scheme.selection_background),
+                });
+                for (i, color) in colors.iter().enumerate() {
+                    let base = match ANSI_NAMES[i % 8] {
+                        "magenta" => "purple",
+                        other => other,
+                    };
+                    let key = if i < 8 {
+                        base.to_string()
+                    } else {
+                        let mut chars = base.chars();
+                        let first = chars.next().unwrap_or_default().to_ascii_uppercase();
+                        format!("bright{first}{}", chars.as_str())
+                    };
+                    entries[key] = hex(color).into();
+                }
+                text += &serde_json::to_string_pretty(&entries).unwrap_or_default();
+                text += "\n";
+            }
+        }
+        text
+    }
+}
+
+// A TOML basic string, quoted and escaped.
+fn toml_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\t' => quoted += "\\t",
+            c if c.is_control() => quoted += &format!("\\u{:04X}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(level: u16) -> Color {
+        Color::from_rgb(level, level, level)
+    }
+
+    #[test]
+    fn ensure_contrast_meets_reachable_targets() {
+        for background in [gray(0), gray(128), gray(255), Color::from_rgb(40, 60, 200)] {
+            let (color, met) = ensure_contrast([0.5, 0.1, 0.3], &background, MIN_CONTRAST);
+            assert!(met, "{}", background.hex);
+            assert!(color.contrast(&background) >= MIN_CONTRAST);
+        }
+    }
+
+    #[test]
+    fn ensure_contrast_reports_unreachable_targets() {
+        // Nothing reaches 7:1 against mid gray.
+        let (color, met) = ensure_contrast([0.5, 0.0, 0.0], &gray(118), STRONG_CONTRAST);
+        assert!(!met);
+        assert!(color.contrast(&gray(118)) < STRONG_CONTRAST);
+    }
+
+    #[test]
+    fn proposed_schemes_contrast_with_their_background() {
+        for background in [gray(10), gray(245), Color::from_rgb(30, 20, 60)] {
+            let scheme = Scheme::propose(&[], true, Some(&background));
+            assert!(scheme.unmet.is_empty(), "{:?}", scheme.unmet);
+            assert!(scheme.min_contrast() >= MIN_CONTRAST);
+            assert!(scheme.foreground.contrast(&background) >= STRONG_CONTRAST);
+        }
+        let mut scheme = Scheme::propose(&[], true, Some(&gray(118)));
+        assert!(scheme.unmet.contains(&Slot::Foreground));
+        scheme.set(Slot::Foreground, gray(0));
+        assert!(!scheme.unmet.contains(&Slot::Foreground));
+    }
+
+    #[test]
+    fn exports() {
+        let mut scheme = Scheme::propose(&[], true, Some(&gray(0)));
+        scheme.colors[5] = Color::from_rgb(0xaa, 0x00, 0xbb);
+        scheme.colors[13] = Color::from_rgb(0xcc, 0x00, 0xdd);
+        let export = |terminal: Terminal| terminal.export("test", &scheme);
+
+        let kitty = export(Terminal::Kitty);
+        assert!(kitty.starts_with("# test\n"));
+        assert!(kitty.contains("background #000000\n"));
+        assert!(kitty.contains("color5 #AA00BB\n"));
+        assert_eq!(kitty.lines().filter(|l| l.starts_with("color")).count(), 16);
+
+        let foot = export(Terminal::Foot);
+        assert!(foot.contains("background=000000\n"));
+        assert!(foot.contains("regular5=aa00bb\n"));
+        assert!(foot.contains("bright5=cc00dd\n"));
+
+        let alacritty = export(Terminal::Alacritty);
+        assert!(alacritty.contains("[colors.normal]\n"));
+        assert!(alacritty.contains("magenta = \"#AA00BB\"\n"));
+
+        let wezterm = export(Terminal::Wezterm);
+        assert!(wezterm.contains("background = \"#000000\"\n"));
+        assert!(wezterm.contains("name = \"test\"\n"));
+
+        assert!(export(Terminal::Xresources).contains("*.color13: #CC00DD\n"));
+
+        let windows = export(Terminal::Windows);
+        assert!(windows.contains("\"purple\": \"#AA00BB\""));
+        assert!(windows.contains("\"brightPurple\": \"#CC00DD\""));
+        assert!(windows.trim_end().ends_with('}'));
+
+        assert_eq!(Terminal::Windows.file_name("test"), "test.json");
+        assert_eq!(Terminal::Foot.file_name("test"), "test.ini");
+    }
+
+    #[test]
+    fn exported_names_are_escaped() {
+        let scheme = Scheme::propose(&[], true, Some(&gray(0)));
+        let name = r#"my "best" wall\2"#;
+        let wezterm = Terminal::Wezterm.export(name, &scheme);
+        assert!(wezterm.contains(r#"name = "my \"best\" wall\\2""#));
+        assert_eq!(toml_string("a\tb\u{7f}"), r#""a\tb\u007F""#);
+
+        let windows = Terminal::Windows.export(name, &scheme);
+        let json: serde_json::Value = serde_json::from_str(&windows).unwrap();
+        assert_eq!(json["name"], name);
+        assert_eq!(json["brightBlack"], scheme.colors[8].hex);
+    }
+}
@@ -0,0 +1,103 @@
+use std::{collections::HashMap, fs};
+
+use crate::paths;
+
+// Tuning read from $XDG_CONFIG_HOME/waycolor/settings.conf, every key is
+// optional:
+//
+//     # Share of the pointer movement applied while Shift is held.
+//     fine_drag_sensitivity = 0.2
+//     # What Ctrl snaps each slider and plane axis to.
+//     snap.h = 15
+//     snap.r = 16
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub fine_drag_sensitivity: f32,
+    pub snap_increments: HashMap<String, u16>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            fine_drag_sensitivity: 0.2,
+            snap_increments: [
+                ("r", 16),
+                ("g", 16),
+                ("b", 16),
+                ("h", 15),
+                ("s", 5),
+                ("v", 5),
+                ("sl", 5),
+                ("l", 5),
+                ("c", 5),
+                ("m", 5),
+                ("y", 5),
+                ("k", 5),
+                ("L", 5),
+                ("C", 5),
+            ]
+            .iter()
+            .map(|(n, i)| (n.to_string(), *i))
+            .collect(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        paths::config_dir()
+            .and_then(|dir| fs::read_to_string(dir.join("waycolor/settings.conf")).ok())
+            .map(|text| parse(&text))
+            .unwrap_or_default()
+    }
+}
+
+// Unknown keys and values that don't parse keep their default.
+fn parse(text: &str) -> Settings {
+    let mut settings = Settings::default();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if key == "fine_drag_sensitivity" {
+            if let Some(sensitivity) = value.parse().ok().filter(|s: &f32| *s > 0.0) {
+                settings.fine_drag_sensitivity = sensitivity;
+            }
+        } else if let Some(label) = key.strip_prefix("snap.") {
+            let increment = value.parse().ok().filter(|i: &u16| *i > 0);
+            if let (Some(slot), Some(increment)) =
+                (settings.snap_increments.get_mut(label), increment)
+            {
+                *slot = increment;
+            }
+        }
+    }
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_only_their_defaults() {
+        let text = "# comment\n\
+                    fine_drag_sensitivity = 0.05\n\
+                    snap.h = 30\n  snap.r=8  \n\
+                    snap.s = 0\nsnap.x = 3\nsnap.v = lots\n";
+        let settings = parse(text);
+        let defaults = Settings::default();
+        assert_eq!(settings.fine_drag_sensitivity, 0.05);
+        assert_eq!(settings.snap_increments["h"], 30);
+        assert_eq!(settings.snap_increments["r"], 8);
+        assert_eq!(settings.snap_increments["s"], defaults.snap_increments["s"]);
+        assert_eq!(settings.snap_increments["v"], defaults.snap_increments["v"]);
+        assert!(!settings.snap_increments.contains_key("x"));
+        assert_eq!(parse(""), defaults);
+        assert_eq!(parse("fine_drag_sensitivity = -1"), defaults);
+    }
+}
@@ -0,0 +1,417 @@
+use std::f32::consts::TAU;
+
+use egui::{Pos2, Rect, Vec2};
+
+use crate::color::{Color, Model};
+
+// Geometry shared by the shaders, the mesh renderer and hit-testing, in
+// points. The ring width is a fraction of the outer radius.
+pub const RING_WIDTH: f32 = 0.18;
+pub const STRIP_WIDTH: f32 = 24.0;
+pub const STRIP_GAP: f32 = 12.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Shape {
+    Plane(Plane),
+    Triangle,
+    Wheel,
+}
+
+// A square spanned by two channels of a color model, with the remaining
+// channel fixed at the current color or, if `strip` is set, on a strip to
+// the right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Plane {
+    pub model: Model,
+    pub x: usize,
+    pub y: usize,
+    pub strip: bool,
+}
+
+// The part of a shape a drag started on, so dragging across the gap between
+// the triangle and its ring doesn't jump from one to the other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Part {
+    Plane,
+    Ring,
+    Strip,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 4] = [
+        Shape::Plane(Plane::SV),
+        Shape::Triangle,
+        Shape::Wheel,
+        Shape::Plane(Plane::HSL),
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            Shape::Plane(Plane::SV) => "SV square".to_string(),
+            Shape::Plane(Plane::HSL) => "HSL square".to_string(),
+            Shape::Plane(plane) => plane.name(),
+            Shape::Triangle => "Hue ring and SV triangle".to_string(),
+            Shape::Wheel => "HS wheel and V strip".to_string(),
+        }
+    }
+
+    pub fn part_at(&self, rect: Rect, pos: Pos2) -> Part {
+        match self {
+            Shape::Plane(plane) => {
+                if plane.strip && pos.x >= strip(rect).min.x - STRIP_GAP * 0.5 {
+                    Part::Strip
+                } else {
+                    Part::Plane
+                }
+            }
+            Shape::Triangle => {
+                let (center, _, inner) = ring(rect);
+                if center.distance(pos) >= inner {
+                    Part::Ring
+                } else {
+                    Part::Plane
+                }
+            }
+            Shape::Wheel => {
+                if pos.x >= strip(rect).min.x - STRIP_GAP * 0.5 {
+                    Part::Strip
+                } else {
+                    Part::Plane
+                }
+            }
+        }
+    }
+
+    // Maps a pointer position on `part` to a color. `snap` gets each picked
+    // value with its channel name before the color is built.
+    pub fn pick(
+        &self,
+        part: Part,
+        rect: Rect,
+        pos: Pos2,
+        color: &Color,
+        snap: impl Fn(&str, f32) -> f32,
+    ) -> Color {
+        let pos = pos.clamp(rect.min, rect.max);
+        let y = 1.0 - (pos.y - rect.min.y) / rect.height();
+        let (h, s, v) = (color.h as f32, color.s as f32, color.v as f32);
+        let (h, s, v) = match (self, part) {
+            (Shape::Plane(plane), _) => {
+                let names = plane.model.channels();
+                let maxes = plane.model.maxes().map(|max| max as f32);
+                let snap = |i: usize, value: f32| snap(names[i], value * maxes[i]) / maxes[i];
+                let mut coords = plane.model.coords(color);
+                if part == Part::Strip {
+                    coords[plane.z()] = snap(plane.z(), y);
+                } else {
+                    let x = (pos.x - rect.min.x) / plane.rect(rect).width();
+                    coords[plane.x] = snap(plane.x, x.min(1.0));
+                    coords[plane.y] = snap(plane.y, y);
+                }
+                return plane.model.color(coords);
+            }
+            (Shape::Triangle, Part::Ring) => {
+                let (center, _, _) = ring(rect);
+                (snap("h", angle(pos - center) * 360.0), s, v)
+            }
+            (Shape::Triangle, _) => {
+                let weights = barycentric(triangle(rect, color), pos).map(|w| w.max(0.0));
+                let sum: f32 = weights.iter().sum();
+                let [a, b, _] = weights.map(|w| w / sum);
+                let v = a + b;
+                let s = if v > 0.0 { a / v } else { 0.0 };
+                (h, snap("s", s * 100.0), snap("v", v * 100.0))
+            }
+            (Shape::Wheel, Part::Strip) => (h, s, snap("v", y * 100.0)),
+            (Shape::Wheel, _) => {
+                let (center, radius) = wheel(rect);
+                let d = pos - center;
+                let s = (d.length() / radius).min(1.0);
+                (snap("h", angle(d) * 360.0), snap("s", s * 100.0), v)
+            }
+        };
+        Color::from_hsv(
+            h.round().rem_euclid(360.0) as u16,
+            s.round().clamp(0.0, 100.0) as u16,
+            v.round().clamp(0.0, 100.0) as u16,
+        )
+    }
+
+    // Handle positions with the color to fill them with. The first one is
+    // the main handle, any others sit on the ring or strip.
+    pub fn handles(&self, rect: Rect, color: &Color) -> Vec<(Pos2, Color)> {
+        let s = color.float_by_name("s");
+        let v = color.float_by_name("v");
+        let hue = Color::from_hsv(color.h, 100, 100);
+        match self {
+            Shape::Plane(plane) => {
+                let coords = plane.model.coords(color);
+                let plane_rect = plane.rect(rect);
+                let pos = Pos2::new(
+                    plane_rect.min.x + plane_rect.width() * coords[plane.x],
+                    rect.max.y - rect.height() * coords[plane.y],
+                );
+                let mut handles = vec![(pos, color.clone())];
+                if plane.strip {
+                    let strip = strip(rect);
+                    let y = strip.max.y - strip.height() * coords[plane.z()];
+                    handles.push((Pos2::new(strip.center().x, y), color.clone()));
+                }
+                handles
+            }
+            Shape::Triangle => {
+                let (center, outer, inner) = ring(rect);
+                let [a, b, c] = triangle(rect, color);
+                let weights = [s * v, (1.0 - s) * v, 1.0 - v];
+                let pos =
+                    a.to_vec2() * weights[0] + b.to_vec2() * weights[1] + c.to_vec2() * weights[2];
+                let ring = center + direction(color) * (outer + inner) * 0.5;
+                vec![(pos.to_pos2(), color.clone()), (ring, hue)]
+            }
+            Shape::Wheel => {
+                let (center, radius) = wheel(rect);
+                let strip = strip(rect);
+                let value = Pos2::new(strip.center().x, strip.max.y - strip.height() * v);
+                vec![
+                    (center + direction(color) * radius * s, color.clone()),
+                    (value, color.clone()),
+                ]
+            }
+        }
+    }
+}
+
+impl Plane {
+    pub const SV: Plane = Plane {
+        model: Model::Hsv,
+        x: 1,
+        y: 2,
+        strip: false,
+    };
+    pub const HSL: Plane = Plane {
+        model: Model::Hsl,
+        x: 1,
+        y: 2,
+        strip: false,
+    };
+
+    pub fn name(&self) -> String {
+        let names = self.model.channels();
+        format!(
+            "{} {}\u{d7}{}",
+            self.model.name(),
+            names[self.x][..1].to_uppercase(),
+            names[self.y][..1].to_uppercase()
+        )
+    }
+
+    // The channel that isn't on either axis.
+    pub fn z(&self) -> usize {
+        3 - self.x - self.y
+    }
+
+    // The area covered by the two axes, leaving room for the strip.
+    pub fn rect(&self, rect: Rect) -> Rect {
+        if self.strip {
+            Rect::from_min_max(
+                rect.min,
+                Pos2::new(strip(rect).min.x - STRIP_GAP, rect.max.y),
+            )
+        } else {
+            rect
+        }
+    }
+}
+
+// Center, outer and inner radius of the hue ring.
+pub fn ring(rect: Rect) -> (Pos2, f32, f32) {
+    let outer = rect.width().min(rect.height()) * 0.5;
+    (rect.center(), outer, outer * (1.0 - RING_WIDTH))
+}
+
+// Center and radius of the HS wheel, which leaves room for the V strip.
+pub fn wheel(rect: Rect) -> (Pos2, f32) {
+    let width = rect.width() - STRIP_WIDTH - STRIP_GAP;
+    let center = Pos2::new(rect.min.x + width * 0.5, rect.center().y);
+    (center, width.min(rect.height()) * 0.5)
+}
+
+pub fn strip(rect: Rect) -> Rect {
+    Rect::from_min_max(Pos2::new(rect.max.x - STRIP_WIDTH, rect.min.y), rect.max)
+}
+
+// Corners of the SV triangle: pure hue, white and black. The hue corner
+// points at the current hue on the ring.
+pub fn triangle(rect: Rect, color: &Color) -> [Pos2; 3] {
+    let (center, _, inner) = ring(rect);
+    let h = color.float_by_name("h") * TAU;
+    [0.0, 1.0, 2.0].map(|i| {
+        let angle = h + i * TAU / 3.0;
+        center + Vec2::new(angle.cos(), -angle.sin()) * inner
+    })
+}
+
+// Hue angle of an offset in screen space, counter-clockwise from the
+// positive x axis, in 0..1.
+fn angle(d: Vec2) -> f32 {
+    ((-d.y).atan2(d.x) / TAU).rem_euclid(1.0)
+}
+
+fn direction(color: &Color) -> Vec2 {
+    let angle = color.float_by_name("h") * TAU;
+    Vec2::new(angle.cos(), -angle.sin())
+}
+
+// Barycentric weights of `p`, negative outside the triangle.
+pub fn barycentric([a, b, c]: [Pos2; 3], p: Pos2) -> [f32; 3] {
+    let (v0, v1, v2) = (b - a, c - a, p - a);
+    let d00 = v0.dot(v0);
+    let d01 = v0.dot(v1);
+    let d11 = v1.dot(v1);
+    let d20 = v2.dot(v0);
+    let d21 = v2.dot(v1);
+    let denom = d00 * d11 - d01 * d01;
+    let wb = (d11 * d20 - d01 * d21) / denom;
+    let wc = (d00 * d21 - d01 * d20) / denom;
+    [1.0 - wb - wc, wb, wc]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    fn rect() -> Rect {
+        Rect::from_min_size(Pos2::new(10.0, 20.0), Vec2::new(240.0, 180.0))
+    }
+
+    // Made from HSV, which the triangle and wheel pick in, so they survive
+    // the trip through whole HSV values.
+    fn colors() -> [Color; 3] {
+        [
+            Color::from_hsv(15, 80, 78),
+            Color::from_hsv(200, 86, 86),
+            Color::from_hsv(300, 45, 90),
+        ]
+    }
+
+    fn planes() -> Vec<Plane> {
+        let mut planes = Vec::new();
+        for model in Model::ALL {
+            for (x, y) in [(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)] {
+                for strip in [false, true] {
+                    planes.push(Plane { model, x, y, strip });
+                }
+            }
+        }
+        planes
+    }
+
+    // Rounding to whole channel values can move a channel by one.
+    fn assert_close(a: &Color, b: &Color, what: &str) {
+        for (x, y) in [(a.r, b.r), (a.g, b.g), (a.b, b.b)] {
+            assert!(x.abs_diff(y) <= 1, "{what}: {} != {}", a.hex, b.hex);
+        }
+    }
+
+    // Picking where each handle is drawn gives back the color.
+    fn assert_round_trip(shape: Shape, parts: &[Part]) {
+        for color in colors() {
+            let handles = shape.handles(rect(), &color);
+            assert_eq!(handles.len(), parts.len(), "{}", shape.name());
+            for ((pos, _), part) in handles.into_iter().zip(parts) {
+                assert_eq!(shape.part_at(rect(), pos), *part, "{}", shape.name());
+                let picked = shape.pick(*part, rect(), pos, &color, |_, value| value);
+                assert_close(&picked, &color, &format!("{} {part:?}", shape.name()));
+            }
+        }
+    }
+
+    #[test]
+    fn planes_round_trip() {
+        for plane in planes() {
+            let parts: &[Part] = match plane.strip {
+                true => &[Part::Plane, Part::Strip],
+                false => &[Part::Plane],
+            };
+            assert_round_trip(Shape::Plane(plane), parts);
+        }
+    }
+
+    #[test]
+    fn triangle_and_wheel_round_trip() {
+        assert_round_trip(Shape::Triangle, &[Part::Plane, Part::Ring]);
+        assert_round_trip(Shape::Wheel, &[Part::Plane, Part::Strip]);
+    }
+
+    #[test]
+    fn picks_snap_by_channel_name() {
+        let names = RefCell::new(Vec::new());
+        let snap = |name: &str, value: f32| {
+            names.borrow_mut().push(name.to_string());
+            (value / 10.0).round() * 10.0
+        };
+        let color = Color::from_hsv(200, 50, 50);
+        let shape = Shape::Plane(Plane::HSL);
+        let pos = Pos2::new(10.0 + 240.0 * 0.43, 20.0 + 180.0 * (1.0 - 0.62));
+        let picked = shape.pick(Part::Plane, rect(), pos, &color, snap);
+        assert_eq!(*names.borrow(), ["sl", "l"]);
+        assert_eq!(picked.value_by_name("sl"), 40);
+        assert_eq!(picked.value_by_name("l"), 60);
+
+        names.borrow_mut().clear();
+        let (center, radius) = wheel(rect());
+        let pos = center + Vec2::new(radius * 0.33, 0.0);
+        let picked = Shape::Wheel.pick(Part::Plane, rect(), pos, &color, snap);
+        assert_eq!(*names.borrow(), ["h", "s"]);
+        assert_eq!((picked.h, picked.s, picked.v), (0, 30, 50));
+    }
+
+    #[test]
+    fn parts() {
+        let rect = rect();
+        let plane = Shape::Plane(Plane::SV);
+        assert_eq!(plane.part_at(rect, rect.right_center()), Part::Plane);
+        let with_strip = Shape::Plane(Plane {
+            strip: true,
+            ..Plane::SV
+        });
+        assert_eq!(with_strip.part_at(rect, rect.center()), Part::Plane);
+        assert_eq!(with_strip.part_at(rect, rect.right_center()), Part::Strip);
+
+        let (center, outer, inner) = ring(rect);
+        assert_eq!(Shape::Triangle.part_at(rect, center), Part::Plane);
+        let on_ring = center + Vec2::new(0.0, -(outer + inner) * 0.5);
+        assert_eq!(Shape::Triangle.part_at(rect, on_ring), Part::Ring);
+
+        let (center, _) = wheel(rect);
+        assert_eq!(Shape::Wheel.part_at(rect, center), Part::Plane);
+        assert_eq!(
+            Shape::Wheel.part_at(rect, strip(rect).center()),
+            Part::Strip
+        );
+    }
+
+    #[test]
+    fn barycentric_weights() {
+        let corners = [
+            Pos2::new(0.0, 0.0),
+            Pos2::new(4.0, 0.0),
+            Pos2::new(0.0, 4.0),
+        ];
+        assert_eq!(barycentric(corners, corners[0]), [1.0, 0.0, 0.0]);
+        assert_eq!(barycentric(corners, corners[1]), [0.0, 1.0, 0.0]);
+        assert_eq!(barycentric(corners, corners[2]), [0.0, 0.0, 1.0]);
+        assert_eq!(barycentric(corners, Pos2::new(2.0, 2.0)), [0.0, 0.5, 0.5]);
+        let outside = barycentric(corners, Pos2::new(4.0, 4.0));
+        assert!(outside[0] < 0.0);
+        assert!((outside.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+
+        // Black sits at the triangle's third corner.
+        let color = Color::from_hsv(90, 0, 0);
+        let black = triangle(rect(), &color)[2];
+        let picked = Shape::Triangle.pick(Part::Plane, rect(), black, &color, |_, v| v);
+        assert_eq!(picked.v, 0);
+    }
+}
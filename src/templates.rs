@@ -0,0 +1,382 @@
+use std::{
+    fs,
+    path::PathBuf,
+    process::Command,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    color::Color,
+    palette::Swatch,
+    paths::{self, expand_home},
+    scheme::{Scheme, Slot},
+};
+
+// Changes closer together than this are rendered once.
+const DEBOUNCE: Duration = Duration::from_millis(400);
+
+// Each template's name with whether it rendered.
+type Results = Vec<(String, Result<(), String>)>;
+
+// A file rendered from `source` to `target` whenever the colors change,
+// followed by `reload` through the shell.
+#[derive(Debug, Clone, Default)]
+pub struct Template {
+    pub name: String,
+    pub source: String,
+    pub target: String,
+    pub reload: String,
+    pub enabled: bool,
+}
+
+// What templates can refer to.
+pub struct Values {
+    pub color: Color,
+    pub palette: Vec<Color>,
+    pub scheme: Option<Scheme>,
+}
+
+impl Values {
+    pub fn new(color: &Color, palette: &[Swatch], scheme: Option<&Scheme>) -> Self {
+        Self {
+            color: color.clone(),
+            palette: palette.iter().map(|s| s.color.clone()).collect(),
+            scheme: scheme.cloned(),
+        }
+    }
+
+    // Cheap to compare, to tell whether anything changed since the last
+    // render.
+    fn key(&self) -> String {
+        let scheme = self.scheme.iter().flat_map(|scheme| {
+            Slot::SPECIAL
+                .into_iter()
+                .chain((0..16).map(Slot::Ansi))
+                .map(|slot| scheme.get(slot).hex.as_str())
+        });
+        std::iter::once(self.color.hex.as_str())
+            .chain(self.palette.iter().map(|c| c.hex.as_str()))
+            .chain(scheme)
+            .collect()
+    }
+}
+
+// Registered templates, kept in $XDG_CONFIG_HOME/waycolor/templates.conf:
+//
+//     [kitty]
+//     template = ~/.config/waycolor/templates/kitty.conf
+//     target = ~/.config/kitty/colors.conf
+//     reload = pkill -USR1 kitty
+//     enabled = true
+pub struct Templates {
+    pub entries: Vec<Template>,
+    // From the latest render.
+    pub results: Results,
+    config: Option<PathBuf>,
+    last_key: Option<String>,
+    changed_at: Option<Instant>,
+    worker: Option<Receiver<Results>>,
+}
+
+impl Templates {
+    pub fn load() -> Self {
+        let config = paths::config_dir().map(|dir| dir.join("waycolor/templates.conf"));
+        let entries = config
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| parse(&text))
+            .unwrap_or_default();
+        Self {
+            entries,
+            results: Vec::new(),
+            config,
+            last_key: None,
+            changed_at: None,
+            worker: None,
+        }
+    }
+
+    pub fn save(&self) -> Result<PathBuf, String> {
+        let path = self.config.clone().ok_or("No config directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        let text: String = self
+            .entries
+            .iter()
+            .map(|t| {
+                format!(
+                    "[{}]\ntemplate = {}\ntarget = {}\nreload = {}\nenabled = {}\n\n",
+                    t.name, t.source, t.target, t.reload, t.enabled
+                )
+            })
+            .collect();
+        fs::write(&path, text).map_err(|err| format!("Failed to save templates: {err}"))?;
+        Ok(path)
+    }
+
+    pub fn is_rendering(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    // Called every frame. The first values seen are taken as already
+    // exported, so starting the app doesn't overwrite anything.
+    pub fn poll(&mut self, ctx: &egui::Context, values: impl FnOnce() -> Values) {
+        if let Some(results) = self.worker.as_ref().and_then(|r| r.try_recv().ok()) {
+            self.results = results;
+            self.worker = None;
+        }
+        if !self.entries.iter().any(|t| t.enabled) {
+            return;
+        }
+        let values = values();
+        let key = values.key();
+        match &self.last_key {
+            None => self.last_key = Some(key),
+            Some(last) if *last != key => {
+                self.last_key = Some(key);
+                self.changed_at = Some(Instant::now());
+            }
+            _ => {}
+        }
+        let Some(changed_at) = self.changed_at else {
+            return;
+        };
+        // A running render repaints when it's done, which brings us back
+        // here for the change that came in meanwhile.
+        if self.worker.is_some() {
+            return;
+        }
+        let elapsed = changed_at.elapsed();
+        if elapsed < DEBOUNCE {
+            ctx.request_repaint_after(DEBOUNCE - elapsed);
+            return;
+        }
+        self.render(ctx, values);
+    }
+
+    pub fn render(&mut self, ctx: &egui::Context, values: Values) {
+        self.changed_at = None;
+        let templates: Vec<Template> = self.entries.iter().filter(|t| t.enabled).cloned().collect();
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(render_all(&templates, &values));
+            ctx.request_repaint();
+        });
+        self.worker = Some(receiver);
+    }
+}
+
+// Writes every template, then runs each distinct reload command once.
+fn render_all(templates: &[Template], values: &Values) -> Results {
+    let mut results = Vec::new();
+    let mut reloads: Vec<&str> = Vec::new();
+    for template in templates {
+        let result = write(template, values);
+        let reload = template.reload.trim();
+        if result.is_ok() && !reload.is_empty() && !reloads.contains(&reload) {
+            reloads.push(reload);
+        }
+        results.push((template.name.clone(), result));
+    }
+    for reload in reloads {
+        let error = match Command::new("sh").args(["-c", reload]).status() {
+            Ok(status) if status.success() => continue,
+            Ok(status) => format!("Reload failed: {status}"),
+            Err(err) => format!("Failed to run: {err}"),
+        };
+        results.push((reload.to_string(), Err(error)));
+    }
+    results
+}
+
+fn write(template: &Template, values: &Values) -> Result<(), String> {
+    let source = expand_home(&template.source);
+    let target = expand_home(&template.target);
+    let text = fs::read_to_string(&source)
+        .map_err(|err| format!("Failed to read {}: {err}", source.display()))?;
+    let rendered = render(&text, values)?;
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    fs::write(&target, rendered)
+        .map_err(|err| format!("Failed to write {}: {err}", target.display()))
+}
+
+// Replaces pywal style placeholders: `{color}` for the picked color,
+// `{palette0}` and up for palette swatches, and `{background}`,
+// `{foreground}`, `{cursor}`, `{selection_background}`,
+// `{selection_foreground}` and `{color0}` to `{color15}` from the terminal
+// scheme. A suffix picks the format, see `format_color`. Literal braces are
+// doubled.
+pub fn render(text: &str, values: &Values) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find(['{', '}']) {
+        out += &rest[..i];
+        let brace = &rest[i..i + 1];
+        let after = &rest[i + 1..];
+        if after.starts_with(brace) {
+            out += brace;
+            rest = &after[1..];
+        } else if brace == "}" {
+            out += brace;
+            rest = after;
+        } else {
+            let end = after.find('}').ok_or("Unclosed placeholder")?;
+            out += &placeholder(&after[..end], values)?;
+            rest = &after[end + 1..];
+        }
+    }
+    out += rest;
+    Ok(out)
+}
+
+fn placeholder(name: &str, values: &Values) -> Result<String, String> {
+    let (base, format) = name.split_once('.').unwrap_or((name, "hex"));
+    let unknown = || format!("Unknown placeholder {{{name}}}");
+    let needs_scheme = || format!("{{{name}}} needs a terminal scheme");
+    let slot = match base {
+        "background" => Some(Slot::Background),
+        "foreground" => Some(Slot::Foreground),
+        "cursor" => Some(Slot::Cursor),
+        "selection_background" => Some(Slot::SelectionBackground),
+        "selection_foreground" => Some(Slot::SelectionForeground),
+        _ => match base.strip_prefix("color").map(str::parse::<usize>) {
+            Some(Ok(i)) if i < 16 => Some(Slot::Ansi(i)),
+            _ => None,
+        },
+    };
+    let color = if base == "color" {
+        &values.color
+    } else if let Some(slot) = slot {
+        values.scheme.as_ref().ok_or_else(needs_scheme)?.get(slot)
+    } else {
+        let index = base
+            .strip_prefix("palette")
+            .and_then(|i| i.parse::<usize>().ok())
+            .ok_or_else(unknown)?;
+        values
+            .palette
+            .get(index)
+            .ok_or_else(|| format!("{{{name}}} is past the end of the palette"))?
+    };
+    format_color(color, format).ok_or_else(unknown)
+}
+
+// hex "#rrggbb", strip "rrggbb", rgb "r,g,b", or a single channel of
+// r, g, b (0..255) or h (0..360), s, v (0..100).
+fn format_color(color: &Color, format: &str) -> Option<String> {
+    Some(match format {
+        "hex" => color.hex.to_lowercase(),
+        "strip" => color.hex.trim_start_matches('#').to_lowercase(),
+        "rgb" => format!("{},{},{}", color.r, color.g, color.b),
+        "r" | "g" | "b" | "h" | "s" | "v" => color.value_by_name(format).to_string(),
+        _ => return None,
+    })
+}
+
+fn parse(text: &str) -> Vec<Template> {
+    let mut entries: Vec<Template> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            entries.push(Template {
+                name: name.to_string(),
+                enabled: true,
+                ..Default::default()
+            });
+            continue;
+        }
+        let (Some(entry), Some((key, value))) = (entries.last_mut(), line.split_once('=')) else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "template" => entry.source = value,
+            "target" => entry.target = value,
+            "reload" => entry.reload = value,
+            "enabled" => entry.enabled = value != "false",
+            _ => {}
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Values {
+        let scheme = Scheme::propose(&[], true, Some(&Color::from_rgb(0, 0, 0)));
+        Values {
+            color: Color::from_rgb(255, 128, 0),
+            palette: vec![Color::from_rgb(1, 2, 3)],
+            scheme: Some(scheme),
+        }
+    }
+
+    #[test]
+    fn render_placeholders() {
+        let values = values();
+        assert_eq!(
+            render("c={color} s={color.strip} rgb={color.rgb} h={color.h}", &values).unwrap(),
+            "c=#ff8000 s=ff8000 rgb=255,128,0 h=30"
+        );
+        assert_eq!(render("{palette0}", &values).unwrap(), "#010203");
+        assert_eq!(render("{background}", &values).unwrap(), "#000000");
+        assert_eq!(render("{{color}} }", &values).unwrap(), "{color} }");
+    }
+
+    #[test]
+    fn render_errors() {
+        let mut values = values();
+        assert!(render("{color", &values).is_err());
+        assert!(render("{nope}", &values).is_err());
+        assert!(render("{color.nope}", &values).is_err());
+        assert!(render("{palette1}", &values).is_err());
+        values.scheme = None;
+        assert!(render("{color3}", &values).is_err());
+    }
+
+    #[test]
+    fn parse_entries() {
+        let text = "# comment\nignored = yes\n\n[kitty]\ntemplate = ~/a.conf\n\
+                    target = /tmp/b.conf \nreload = kill -USR1 kitty\n\
+                    [waybar]\nenabled = false\n";
+        let templates = parse(text);
+        assert_eq!(templates.len(), 2);
+        assert_eq!(templates[0].name, "kitty");
+        assert_eq!(templates[0].source, "~/a.conf");
+        assert_eq!(templates[0].target, "/tmp/b.conf");
+        assert_eq!(templates[0].reload, "kill -USR1 kitty");
+        assert!(templates[0].enabled);
+        assert_eq!(templates[1].name, "waybar");
+        assert!(!templates[1].enabled);
+    }
+
+    #[test]
+    fn render_all_reports_failed_reloads() {
+        let dir = std::env::temp_dir().join(format!("waycolor-templates-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source");
+        fs::write(&source, "{color}").unwrap();
+        let template = |name: &str, reload: &str| Template {
+            name: name.to_string(),
+            source: source.to_string_lossy().to_string(),
+            target: dir.join(name).to_string_lossy().to_string(),
+            reload: reload.to_string(),
+            enabled: true,
+        };
+        let results = render_all(&[template("ok", "true"), template("bad", "exit 3")], &values());
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(results.len(), 3);
+        assert!(results[0].1.is_ok() && results[1].1.is_ok());
+        assert_eq!(results[2].0, "exit 3");
+        assert!(results[2].1.is_err());
+    }
+}
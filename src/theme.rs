@@ -1,5 +1,14 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::Duration,
+};
+
 use egui::{style, Color32, Visuals};
 
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
 pub const THEME: Theme = {
     Theme {
         fg: Color32::from_rgb(214, 216, 220),
@@ -11,6 +20,7 @@ pub const THEME: Theme = {
     }
 };
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Theme {
     pub fg: Color32,
     pub bg: Color32,
@@ -21,9 +31,51 @@ pub struct Theme {
 }
 
 impl Theme {
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = fs::read_to_string(path).ok()?;
+        match path.extension()?.to_str()? {
+            "json" => Self::from_pywal(&text),
+            "yaml" | "yml" => Self::from_base16(&text),
+            _ => None,
+        }
+    }
+
+    // pywal's colors.json: "special" holds background/foreground,
+    // color8 is the bright black used for comments and muted text.
+    pub fn from_pywal(text: &str) -> Option<Self> {
+        let json: serde_json::Value = serde_json::from_str(text).ok()?;
+        let get = |group: &str, key: &str| json[group][key].as_str().and_then(hex_color);
+        let bg = get("special", "background")?;
+        let fg = get("special", "foreground")?;
+        Some(Theme {
+            fg,
+            bg,
+            bg_dark: darken(bg, 0.75),
+            bg_light: mix(bg, fg, 0.08),
+            bg_selected: mix(bg, fg, 0.15),
+            fg_selected: get("colors", "color8").unwrap_or(mix(bg, fg, 0.45)),
+        })
+    }
+
+    // base16: base00 default bg, base01 lighter bg, base02 selection bg,
+    // base03 comments, base05 default fg.
+    pub fn from_base16(text: &str) -> Option<Self> {
+        let pairs = parse_pairs(text);
+        let get = |key: &str| find_color(&pairs, key);
+        let bg = get("base00")?;
+        Some(Theme {
+            fg: get("base05")?,
+            bg,
+            bg_dark: darken(bg, 0.75),
+            bg_light: get("base01")?,
+            bg_selected: get("base02")?,
+            fg_selected: get("base03")?,
+        })
+    }
+
     pub fn visuals(&self, old: Visuals) -> egui::Visuals {
         Visuals {
-            dark_mode: true,
+            dark_mode: luma(self.bg) < 0.5,
             override_text_color: Some(self.fg),
             hyperlink_color: self.fg,
             faint_bg_color: self.bg_light,
@@ -68,3 +120,133 @@ impl Theme {
         }
     }
 }
+
+// Checks the scheme file on a thread, so pywal can run before or after
+// WayColor starts and the UI thread never touches the disk.
+pub struct ThemeWatcher {
+    receiver: Receiver<Theme>,
+    _stop: Sender<()>,
+}
+
+impl ThemeWatcher {
+    // $WAYCOLOR_THEME takes precedence, otherwise follow pywal.
+    pub fn start(ctx: &egui::Context) -> Option<Self> {
+        let path = match std::env::var_os("WAYCOLOR_THEME") {
+            Some(path) => PathBuf::from(path),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".cache/wal/colors.json"),
+        };
+        let (sender, receiver) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel();
+        let ctx = ctx.clone();
+        // The thread ends once the watcher is dropped, which hangs up `stop`.
+        std::thread::spawn(move || {
+            let mut modified = None;
+            loop {
+                let current = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                if current.is_some() && current != modified {
+                    modified = current;
+                    if let Some(theme) = Theme::load(&path) {
+                        if sender.send(theme).is_err() {
+                            return;
+                        }
+                        ctx.request_repaint();
+                    }
+                }
+                if stopped.recv_timeout(WATCH_INTERVAL) != Err(RecvTimeoutError::Timeout) {
+                    return;
+                }
+            }
+        });
+        Some(ThemeWatcher {
+            receiver,
+            _stop: stop,
+        })
+    }
+
+    pub fn poll(&self) -> Option<Theme> {
+        self.receiver.try_iter().last()
+    }
+}
+
+// Collects the flat `key: value` pairs of a base16 YAML scheme, which is
+// all it needs.
+fn parse_pairs(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter_map(|entry| {
+            let (key, value) = entry.split_once(':')?;
+            let key = key.trim().trim_matches(['"', '\'']);
+            let value = value.trim();
+            let value = match value.strip_prefix(['"', '\'']) {
+                Some(quoted) => quoted.split(['"', '\'']).next()?,
+                None => value.split_whitespace().next()?,
+            };
+            (!key.is_empty()).then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn find_color(pairs: &[(String, String)], key: &str) -> Option<Color32> {
+    let (_, value) = pairs.iter().find(|(k, _)| k == key)?;
+    hex_color(value)
+}
+
+fn hex_color(value: &str) -> Option<Color32> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    Color32::from_hex(&format!("#{hex}")).ok()
+}
+
+// Rough perceived lightness in 0..1, enough to tell light schemes apart.
+fn luma(color: Color32) -> f32 {
+    (0.2126 * color.r() as f32 + 0.7152 * color.g() as f32 + 0.0722 * color.b() as f32) / 255.0
+}
+
+fn darken(color: Color32, t: f32) -> Color32 {
+    let scale = |c: u8| (c as f32 * t) as u8;
+    Color32::from_rgb(scale(color.r()), scale(color.g()), scale(color.b()))
+}
+
+fn mix(a: Color32, b: Color32, t: f32) -> Color32 {
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+    Color32::from_rgb(lerp(a.r(), b.r()), lerp(a.g(), b.g()), lerp(a.b(), b.b()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn parse_pairs_reads_yaml() {
+        let yaml = "scheme: 'Tokyo Night'\nbase00: \"1a1b26\" # bg\nbase05: c0caf5\n";
+        assert_eq!(
+            parse_pairs(yaml),
+            [
+                pair("scheme", "Tokyo Night"),
+                pair("base00", "1a1b26"),
+                pair("base05", "c0caf5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn pywal_and_base16_themes() {
+        let pywal = r##"{"wallpaper": "/home/me/Pictures/a, \"b\": c.png",
+            "special":{"background":"#ffffff","foreground":"#000000"},
+            "colors": {"color8": "#808080"}}"##;
+        let theme = Theme::from_pywal(pywal).unwrap();
+        assert_eq!(theme.bg, Color32::WHITE);
+        assert_eq!(theme.fg, Color32::BLACK);
+        assert_eq!(theme.fg_selected, Color32::from_rgb(128, 128, 128));
+        assert!(Theme::from_pywal("{}").is_none());
+
+        let base16 = "base00: \"282c34\"\nbase01: \"353b45\"\nbase02: \"3e4451\"\n\
+                      base03: \"545862\"\nbase05: \"abb2bf\"\n";
+        let theme = Theme::from_base16(base16).unwrap();
+        assert_eq!(theme.bg, Color32::from_rgb(0x28, 0x2c, 0x34));
+        assert_eq!(theme.bg_selected, Color32::from_rgb(0x3e, 0x44, 0x51));
+        assert!(Theme::from_base16("base00: \"282c34\"").is_none());
+    }
+}
@@ -0,0 +1,572 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use zbus::{
+    blocking::{fdo::DBusProxy, Connection, ConnectionBuilder, Proxy},
+    dbus_interface,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Structure, Value},
+    SignalContext,
+};
+
+use image::RgbaImage;
+
+use crate::{
+    color::Color,
+    ipc::{Command, Request},
+};
+
+const WATCHER: &str = "org.kde.StatusNotifierWatcher";
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const ICON: &[u8] = include_bytes!("../picker_icon.png");
+
+// Menu item ids, history entries count up from HISTORY.
+const PICK: i32 = 1;
+const TOGGLE: i32 = 2;
+const COPY: i32 = 3;
+const QUIT: i32 = 4;
+const SEPARATOR: i32 = 5;
+const HISTORY_SEPARATOR: i32 = 6;
+const HISTORY: i32 = 100;
+// Dragging a slider changes the color every frame, bars get told at most
+// this often.
+const SIGNAL_INTERVAL: Duration = Duration::from_millis(200);
+
+// An icon in the tray's format, ARGB32 in network byte order.
+type Pixmap = (i32, i32, Vec<u8>);
+type Properties = HashMap<String, OwnedValue>;
+
+// What the tray shows, copied over from the app whenever it changes.
+#[derive(Default)]
+struct State {
+    color: Color,
+    hidden: bool,
+    history: Vec<Color>,
+    revision: u32,
+}
+
+// A StatusNotifierItem, which is what waybar and most other bars show in
+// their tray, with its menu through com.canonical.dbusmenu. Clicking the
+// icon shows or hides the window, the menu sends commands like the
+// control socket does.
+pub struct Tray {
+    connection: Connection,
+    state: Arc<Mutex<State>>,
+    errors: Receiver<String>,
+    ctx: egui::Context,
+    // Changes not signalled yet, and when the last signal went out.
+    icon_changed: bool,
+    menu_changed: bool,
+    signalled_at: Option<Instant>,
+}
+
+impl Tray {
+    pub fn start(ctx: egui::Context, requests: Sender<Request>) -> Result<Self, String> {
+        let state = Arc::new(Mutex::new(State::default()));
+        let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+        let item = Item {
+            state: state.clone(),
+            requests: requests.clone(),
+            ctx: ctx.clone(),
+            icon: image::load_from_memory(ICON)
+                .ok()
+                .map(|icon| icon.to_rgba8()),
+            tinted: Mutex::new(None),
+        };
+        let menu = Menu {
+            state: state.clone(),
+            requests,
+            ctx: ctx.clone(),
+        };
+        let connection = ConnectionBuilder::session()
+            .and_then(|builder| builder.serve_at(ITEM_PATH, item))
+            .and_then(|builder| builder.serve_at(MENU_PATH, menu))
+            .and_then(|builder| builder.name(name.as_str()))
+            .and_then(|builder| builder.build())
+            .map_err(|err| err.to_string())?;
+        let (sender, errors) = mpsc::channel();
+        watch(connection.clone(), name, sender);
+        Ok(Self {
+            connection,
+            state,
+            errors,
+            ctx,
+            icon_changed: false,
+            menu_changed: false,
+            signalled_at: None,
+        })
+    }
+
+    // Registering again after the bar restarts happens on a thread, which
+    // leaves its failures here.
+    pub fn take_error(&self) -> Option<String> {
+        self.errors.try_iter().last()
+    }
+
+    // Called every frame. The state properties are read from is always
+    // current, signals for what changed go out at most every
+    // SIGNAL_INTERVAL, with the last change signalled once it has passed.
+    pub fn update(&mut self, color: &Color, hidden: bool, history: &[Color]) -> Result<(), String> {
+        let revision = {
+            let Ok(mut state) = self.state.lock() else {
+                return Ok(());
+            };
+            let icon = state.color.hex != color.hex;
+            let menu = icon
+                || state.hidden != hidden
+                || !state
+                    .history
+                    .iter()
+                    .map(|c| &c.hex)
+                    .eq(history.iter().map(|c| &c.hex));
+            if menu {
+                state.color = color.clone();
+                state.hidden = hidden;
+                state.history = history.to_vec();
+                state.revision += 1;
+            }
+            self.icon_changed |= icon;
+            self.menu_changed |= menu;
+            state.revision
+        };
+        if !self.menu_changed {
+            return Ok(());
+        }
+        let elapsed = self.signalled_at.map_or(SIGNAL_INTERVAL, |at| at.elapsed());
+        if elapsed < SIGNAL_INTERVAL {
+            self.ctx.request_repaint_after(SIGNAL_INTERVAL - elapsed);
+            return Ok(());
+        }
+        let icon = std::mem::take(&mut self.icon_changed);
+        self.menu_changed = false;
+        self.signalled_at = Some(Instant::now());
+        let result = zbus::block_on(async {
+            let connection = self.connection.inner();
+            if icon {
+                let ctxt = SignalContext::new(connection, ITEM_PATH)?;
+                Item::new_icon(&ctxt).await?;
+                Item::new_tool_tip(&ctxt).await?;
+            }
+            let ctxt = SignalContext::new(connection, MENU_PATH)?;
+            Menu::layout_updated(&ctxt, revision, 0).await?;
+            zbus::Result::Ok(())
+        });
+        result.map_err(|err| format!("Failed to update tray item: {err}"))
+    }
+}
+
+// Registers with the watcher, again whenever a new one shows up, so the
+// item comes back when the bar restarts. Without a watcher yet there's
+// nothing to report, a bar may still start.
+fn watch(connection: Connection, name: String, errors: Sender<String>) {
+    std::thread::spawn(move || {
+        let register = || {
+            Proxy::new(&connection, WATCHER, "/StatusNotifierWatcher", WATCHER)
+                .and_then(|proxy| proxy.call_method("RegisterStatusNotifierItem", &(&name,)))
+        };
+        let _ = register();
+        let Ok(owners) = DBusProxy::new(&connection)
+            .and_then(|proxy| proxy.receive_name_owner_changed_with_args(&[(0, WATCHER)]))
+        else {
+            return;
+        };
+        for signal in owners {
+            let appeared = signal.args().is_ok_and(|args| args.new_owner().is_some());
+            if appeared {
+                if let Err(err) = register() {
+                    let _ = errors.send(format!("Failed to register tray item: {err}"));
+                }
+            }
+        }
+    });
+}
+
+fn send(requests: &Sender<Request>, ctx: &egui::Context, command: Command) {
+    let _ = requests.send(Request::local(command));
+    ctx.request_repaint();
+}
+
+struct Item {
+    state: Arc<Mutex<State>>,
+    requests: Sender<Request>,
+    ctx: egui::Context,
+    // Decoded once, and tinted again only when the color changed.
+    icon: Option<RgbaImage>,
+    tinted: Mutex<Option<(String, Pixmap)>>,
+}
+
+#[dbus_interface(name = "org.kde.StatusNotifierItem")]
+impl Item {
+    fn activate(&self, _x: i32, _y: i32) {
+        send(&self.requests, &self.ctx, Command::Toggle);
+    }
+
+    fn secondary_activate(&self, _x: i32, _y: i32) {
+        send(&self.requests, &self.ctx, Command::Pick);
+    }
+
+    // The menu is shown by the host through `Menu`.
+    fn context_menu(&self, _x: i32, _y: i32) {}
+
+    fn scroll(&self, _delta: i32, _orientation: &str) {}
+
+    #[dbus_interface(property)]
+    fn category(&self) -> &str {
+        "ApplicationStatus"
+    }
+
+    #[dbus_interface(property)]
+    fn id(&self) -> &str {
+        "waycolor"
+    }
+
+    #[dbus_interface(property)]
+    fn title(&self) -> &str {
+        "WayColor"
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> &str {
+        "Active"
+    }
+
+    #[dbus_interface(property)]
+    fn icon_name(&self) -> &str {
+        ""
+    }
+
+    #[dbus_interface(property)]
+    fn icon_pixmap(&self) -> Vec<Pixmap> {
+        let (Some(icon), Ok(mut tinted)) = (self.icon.as_ref(), self.tinted.lock()) else {
+            return Vec::new();
+        };
+        let color = self.color();
+        match tinted.as_ref() {
+            Some((hex, pixmap)) if *hex == color.hex => vec![pixmap.clone()],
+            _ => {
+                let pixmap = tinted_icon(icon, &color);
+                *tinted = Some((color.hex, pixmap.clone()));
+                vec![pixmap]
+            }
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn tool_tip(&self) -> (String, Vec<Pixmap>, String, String) {
+        let color = self.color();
+        (
+            String::new(),
+            Vec::new(),
+            String::from("WayColor"),
+            color.hex,
+        )
+    }
+
+    #[dbus_interface(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        ObjectPath::from_static_str_unchecked(MENU_PATH).into()
+    }
+
+    #[dbus_interface(signal)]
+    async fn new_icon(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn new_tool_tip(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+}
+
+impl Item {
+    fn color(&self) -> Color {
+        self.state
+            .lock()
+            .map(|state| state.color.clone())
+            .unwrap_or_default()
+    }
+}
+
+struct Menu {
+    state: Arc<Mutex<State>>,
+    requests: Sender<Request>,
+    ctx: egui::Context,
+}
+
+#[dbus_interface(name = "com.canonical.dbusmenu")]
+impl Menu {
+    // The menu is flat, so the root is the only item with children.
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        _recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> (u32, (i32, Properties, Vec<OwnedValue>)) {
+        let (revision, items) = self.layout();
+        let children = match parent_id {
+            0 => items
+                .into_iter()
+                .map(|(id, properties)| {
+                    let child = Structure::from((id, properties, Vec::<Value>::new()));
+                    Value::from(child).into()
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let root = HashMap::from([(
+            String::from("children-display"),
+            Value::from("submenu").into(),
+        )]);
+        (revision, (parent_id, root, children))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, Properties)> {
+        let (_, items) = self.layout();
+        items
+            .into_iter()
+            .filter(|(id, _)| ids.is_empty() || ids.contains(id))
+            .collect()
+    }
+
+    fn event(&self, id: i32, event_id: &str, _data: Value<'_>, _timestamp: u32) {
+        if event_id == "clicked" {
+            self.clicked(id);
+        }
+    }
+
+    fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+        for (id, event_id, _, _) in events {
+            if event_id == "clicked" {
+                self.clicked(id);
+            }
+        }
+        Vec::new()
+    }
+
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    fn about_to_show_group(&self, _ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        (Vec::new(), Vec::new())
+    }
+
+    #[dbus_interface(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[dbus_interface(property)]
+    fn text_direction(&self) -> &str {
+        "ltr"
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> &str {
+        "normal"
+    }
+
+    #[dbus_interface(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[dbus_interface(signal)]
+    async fn layout_updated(
+        ctxt: &SignalContext<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+}
+
+impl Menu {
+    fn layout(&self) -> (u32, Vec<(i32, Properties)>) {
+        match self.state.lock() {
+            Ok(state) => (state.revision, items(&state)),
+            Err(_) => (0, Vec::new()),
+        }
+    }
+
+    fn clicked(&self, id: i32) {
+        let command = match self.state.lock() {
+            Ok(state) => command(id, &state.history),
+            Err(_) => None,
+        };
+        if let Some(command) = command {
+            send(&self.requests, &self.ctx, command);
+        }
+    }
+}
+
+fn items(state: &State) -> Vec<(i32, Properties)> {
+    let toggle = if state.hidden {
+        "Show window"
+    } else {
+        "Hide window"
+    };
+    let mut items = vec![
+        (PICK, label("Pick from screen")),
+        (TOGGLE, label(toggle)),
+        (COPY, label(&format!("Copy {}", state.color.hex))),
+    ];
+    if !state.history.is_empty() {
+        items.push((HISTORY_SEPARATOR, separator()));
+    }
+    for (i, color) in state.history.iter().enumerate() {
+        let mut properties = label(&color.hex);
+        if let Some(png) = swatch_png(color) {
+            properties.insert(String::from("icon-data"), Value::from(png).into());
+        }
+        items.push((HISTORY + i as i32, properties));
+    }
+    items.push((SEPARATOR, separator()));
+    items.push((QUIT, label("Quit")));
+    items
+}
+
+// What clicking a menu item asks the app to do, history entries set their
+// color.
+fn command(id: i32, history: &[Color]) -> Option<Command> {
+    match id {
+        PICK => Some(Command::Pick),
+        TOGGLE => Some(Command::Toggle),
+        COPY => Some(Command::Copy),
+        QUIT => Some(Command::Quit),
+        _ => {
+            let index = usize::try_from(id.checked_sub(HISTORY)?).ok()?;
+            history.get(index).cloned().map(Command::SetColor)
+        }
+    }
+}
+
+fn label(text: &str) -> Properties {
+    HashMap::from([(String::from("label"), Value::from(text).into())])
+}
+
+fn separator() -> Properties {
+    HashMap::from([(String::from("type"), Value::from("separator").into())])
+}
+
+// The picker icon, colored like the in-app button but with the current
+// color instead of the theme's.
+fn tinted_icon(icon: &RgbaImage, color: &Color) -> Pixmap {
+    let tint = |c: u8, t: u16| (c as u32 * t as u32 / 255) as u8;
+    let data = icon
+        .pixels()
+        .flat_map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            [a, tint(r, color.r), tint(g, color.g), tint(b, color.b)]
+        })
+        .collect();
+    (icon.width() as i32, icon.height() as i32, data)
+}
+
+// A small square of the color for history entries, dbusmenu takes PNG.
+fn swatch_png(color: &Color) -> Option<Vec<u8>> {
+    let rgb = [color.r, color.g, color.b].map(|c| c as u8);
+    let image =
+        image::RgbaImage::from_fn(16, 16, |_, _| image::Rgba([rgb[0], rgb[1], rgb[2], 255]));
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .ok()?;
+    Some(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(items: &[(i32, Properties)]) -> Vec<(i32, String)> {
+        items
+            .iter()
+            .map(|(id, properties)| {
+                let text = match (properties.get("label"), properties.get("type")) {
+                    (Some(label), _) => String::try_from(label.clone()).unwrap(),
+                    (None, Some(_)) => String::from("-"),
+                    (None, None) => String::new(),
+                };
+                (*id, text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn menu_layout() {
+        let state = State {
+            color: Color::from_rgb(1, 2, 3),
+            ..State::default()
+        };
+        assert_eq!(
+            labels(&items(&state)),
+            [
+                (PICK, String::from("Pick from screen")),
+                (TOGGLE, String::from("Hide window")),
+                (COPY, String::from("Copy #010203")),
+                (SEPARATOR, String::from("-")),
+                (QUIT, String::from("Quit")),
+            ]
+        );
+
+        let state = State {
+            hidden: true,
+            history: vec![Color::from_rgb(255, 0, 0), Color::from_rgb(0, 0, 255)],
+            ..state
+        };
+        let items = items(&state);
+        assert_eq!(
+            labels(&items),
+            [
+                (PICK, String::from("Pick from screen")),
+                (TOGGLE, String::from("Show window")),
+                (COPY, String::from("Copy #010203")),
+                (HISTORY_SEPARATOR, String::from("-")),
+                (HISTORY, String::from("#FF0000")),
+                (HISTORY + 1, String::from("#0000FF")),
+                (SEPARATOR, String::from("-")),
+                (QUIT, String::from("Quit")),
+            ]
+        );
+        assert!(items[4].1.contains_key("icon-data"));
+    }
+
+    #[test]
+    fn clicks_map_to_commands() {
+        let history = [Color::from_rgb(255, 0, 0), Color::from_rgb(0, 0, 255)];
+        assert!(matches!(command(PICK, &history), Some(Command::Pick)));
+        assert!(matches!(command(TOGGLE, &history), Some(Command::Toggle)));
+        assert!(matches!(command(COPY, &history), Some(Command::Copy)));
+        assert!(matches!(command(QUIT, &history), Some(Command::Quit)));
+        let hex = |id| match command(id, &history) {
+            Some(Command::SetColor(color)) => Some(color.hex),
+            _ => None,
+        };
+        assert_eq!(hex(HISTORY).as_deref(), Some("#FF0000"));
+        assert_eq!(hex(HISTORY + 1).as_deref(), Some("#0000FF"));
+        assert!(command(HISTORY + 2, &history).is_none());
+        assert!(command(SEPARATOR, &history).is_none());
+        assert!(command(HISTORY_SEPARATOR, &history).is_none());
+        assert!(command(0, &history).is_none());
+    }
+
+    #[test]
+    fn icon_is_tinted_with_the_color() {
+        let icon = RgbaImage::from_raw(2, 1, vec![255, 255, 255, 255, 128, 0, 255, 0]).unwrap();
+        let (width, height, data) = tinted_icon(&icon, &Color::from_rgb(255, 128, 0));
+        assert_eq!((width, height), (2, 1));
+        // ARGB, each channel scaled by the color.
+        assert_eq!(data, [255, 255, 128, 0, 0, 128, 0, 0]);
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::paths::expand_home;
+
+// Finds the wallpaper currently shown, asking the running wallpaper daemon
+// first and falling back to its config. Only the first output's wallpaper
+// is used when they differ.
+pub fn locate() -> Result<PathBuf, String> {
+    let home = PathBuf::from(std::env::var_os("HOME").unwrap_or_default());
+    swww()
+        .or_else(hyprpaper_active)
+        .or_else(|| hyprpaper_config(&home))
+        .or_else(swaybg_process)
+        .or_else(|| sway_config(&home))
+        .map(|path| expand_home(&path))
+        .filter(|path| path.is_file())
+        .ok_or_else(|| String::from("No wallpaper found from swww, hyprpaper or swaybg"))
+}
+
+// Each source's output or config, handed to the parsers below.
+fn swww() -> Option<String> {
+    parse_swww(&command_output("swww", &["query"])?)
+}
+
+fn hyprpaper_active() -> Option<String> {
+    parse_hyprpaper_active(&command_output("hyprctl", &["hyprpaper", "listactive"])?)
+}
+
+fn hyprpaper_config(home: &Path) -> Option<String> {
+    parse_hyprpaper_config(&fs::read_to_string(home.join(".config/hypr/hyprpaper.conf")).ok()?)
+}
+
+fn swaybg_process() -> Option<String> {
+    fs::read_dir("/proc").ok()?.flatten().find_map(|entry| {
+        let cmdline = fs::read(entry.path().join("cmdline")).ok()?;
+        let args: Vec<String> = cmdline
+            .split(|b| *b == 0)
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect();
+        parse_swaybg_args(&args)
+    })
+}
+
+fn sway_config(home: &Path) -> Option<String> {
+    parse_sway_config(&fs::read_to_string(home.join(".config/sway/config")).ok()?)
+}
+
+// `swww query` prints one line per output:
+// "eDP-1: 1920x1080, scale: 1, currently displaying: image: /path"
+fn parse_swww(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        line.split_once("image: ")
+            .map(|(_, path)| path.trim().to_string())
+    })
+}
+
+// "eDP-1 = /path" per output.
+fn parse_hyprpaper_active(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| {
+            line.split_once(" = ")
+                .map(|(_, path)| path.trim().to_string())
+        })
+        .filter(|path| !path.is_empty())
+}
+
+// "wallpaper = monitor,path", where the monitor may be empty.
+fn parse_hyprpaper_config(text: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        let (_, path) = value.split_once(',')?;
+        (key.trim() == "wallpaper").then(|| path.trim().to_string())
+    })
+}
+
+// swaybg is configured on its command line, `-i path` or `--image path`.
+fn parse_swaybg_args(args: &[String]) -> Option<String> {
+    if !args.first()?.ends_with("swaybg") {
+        return None;
+    }
+    let index = args.iter().position(|a| a == "-i" || a == "--image")?;
+    args.get(index + 1).cloned()
+}
+
+// "output <name> bg <path> <mode>" in sway's config.
+fn parse_sway_config(text: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let bg = words
+            .iter()
+            .position(|w| *w == "bg" || *w == "background")?;
+        // Paths with spaces are quoted, the mode always comes last.
+        let path = words.get(bg + 1..words.len().checked_sub(1)?)?.join(" ");
+        // Solid color backgrounds are given as "#rrggbb solid_color".
+        (words.first() == Some(&"output") && !path.is_empty() && !path.starts_with('#'))
+            .then(|| path.trim_matches(['"', '\'']).to_string())
+    })
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swww_query() {
+        let output = "\
+            eDP-1: 1920x1080, scale: 1, currently displaying: color: 000000\n\
+            DP-1: 2560x1440, scale: 1, currently displaying: image: /home/me/a b.png\n";
+        assert_eq!(parse_swww(output).as_deref(), Some("/home/me/a b.png"));
+        assert_eq!(
+            parse_swww("eDP-1: currently displaying: color: 000000"),
+            None
+        );
+    }
+
+    #[test]
+    fn hyprpaper_listactive() {
+        let output = "eDP-1 = /home/me/wall.png\nDP-1 = /home/me/other.png\n";
+        assert_eq!(
+            parse_hyprpaper_active(output).as_deref(),
+            Some("/home/me/wall.png")
+        );
+        assert_eq!(parse_hyprpaper_active("no wallpapers active"), None);
+        assert_eq!(parse_hyprpaper_active("eDP-1 = \n"), None);
+    }
+
+    #[test]
+    fn hyprpaper_conf() {
+        let text = "\
+            preload = ~/walls/a.png\n\
+            # wallpaper = eDP-1,~/walls/old.png\n\
+            wallpaper = ,~/walls/a.png\n\
+            wallpaper = DP-1,~/walls/b.png\n";
+        assert_eq!(
+            parse_hyprpaper_config(text).as_deref(),
+            Some("~/walls/a.png")
+        );
+        let text = "wallpaper=eDP-1, /walls/c d.png\n";
+        assert_eq!(
+            parse_hyprpaper_config(text).as_deref(),
+            Some("/walls/c d.png")
+        );
+        assert_eq!(parse_hyprpaper_config("preload = /walls/a.png"), None);
+    }
+
+    #[test]
+    fn swaybg_command_line() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        assert_eq!(
+            parse_swaybg_args(&args("/usr/bin/swaybg -o * -i /walls/a.png -m fill")).as_deref(),
+            Some("/walls/a.png")
+        );
+        assert_eq!(
+            parse_swaybg_args(&args("swaybg --image /walls/b.png")).as_deref(),
+            Some("/walls/b.png")
+        );
+        assert_eq!(parse_swaybg_args(&args("swaybg -c #000000")), None);
+        assert_eq!(parse_swaybg_args(&args("feh -i /walls/a.png")), None);
+        assert_eq!(parse_swaybg_args(&[]), None);
+    }
+
+    #[test]
+    fn sway_config_outputs() {
+        let text = "\
+            # output * bg ~/old.png fill\n\
+            exec swaybg -i ~/not-this.png\n\
+            output * bg \"~/My Pictures/wall.png\" fill\n";
+        assert_eq!(
+            parse_sway_config(text).as_deref(),
+            Some("~/My Pictures/wall.png")
+        );
+        let text = "output eDP-1 background /walls/a.png stretch";
+        assert_eq!(parse_sway_config(text).as_deref(), Some("/walls/a.png"));
+        assert_eq!(parse_sway_config("output * bg #112233 solid_color"), None);
+        assert_eq!(parse_sway_config("output * bg"), None);
+        assert_eq!(parse_sway_config("output eDP-1 scale 2"), None);
+    }
+}
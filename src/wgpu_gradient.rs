@@ -0,0 +1,654 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use eframe::{egui_wgpu, wgpu};
+use egui::{Painter, Rect};
+
+use crate::{
+    color::{max_by_name, Color, Model, OKLCH_MAX_CHROMA},
+    gradient::{model_coords, GradientPainter, GradientType, RenderOptions},
+    mesh::MeshPainter,
+    shape::{Plane, Shape, RING_WIDTH, STRIP_GAP, STRIP_WIDTH},
+};
+
+pub struct WgpuPainter {
+    failed: Arc<Mutex<Failed>>,
+}
+
+// Gradients whose pipeline couldn't be created when first prepared, and
+// the error that hasn't been reported yet.
+#[derive(Default)]
+struct Failed {
+    gtypes: HashSet<GradientType>,
+    error: Option<String>,
+}
+
+impl WgpuPainter {
+    // Pipelines live in egui-wgpu's callback resources, where the paint
+    // callbacks can reach them. Gradients missing from `gtypes` get theirs
+    // when first prepared.
+    pub fn new(
+        render_state: &egui_wgpu::RenderState,
+        gtypes: &[GradientType],
+    ) -> Result<Self, String> {
+        let device = &render_state.device;
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gradient"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("gradient"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let mut resources = WgpuGradients {
+            bind_group_layout,
+            pipeline_layout,
+            target_format: render_state.target_format,
+            gradients: HashMap::new(),
+        };
+        for gtype in gtypes {
+            resources.insert(device, gtype)?;
+        }
+
+        render_state
+            .renderer
+            .write()
+            .callback_resources
+            .insert(resources);
+        Ok(Self {
+            failed: Arc::new(Mutex::new(Failed::default())),
+        })
+    }
+}
+
+impl GradientPainter for WgpuPainter {
+    fn name(&self) -> &'static str {
+        "wgpu"
+    }
+
+    fn paint(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        gtype: &GradientType,
+        color: Color,
+        options: RenderOptions,
+    ) {
+        if self.failed.lock().unwrap().gtypes.contains(gtype) {
+            MeshPainter.paint(painter, rect, gtype, color, options);
+            return;
+        }
+        let [m0, m1, m2] = model_coords(gtype, &color);
+        let [p0, p1, p2, p3] = options.params();
+        painter.add(egui_wgpu::Callback::new_paint_callback(
+            rect,
+            GradientCallback {
+                gtype: gtype.clone(),
+                failed: self.failed.clone(),
+                ctx: painter.ctx().clone(),
+                uniforms: [
+                    color.float_by_name("r"),
+                    color.float_by_name("g"),
+                    color.float_by_name("b"),
+                    1.0,
+                    color.float_by_name("h"),
+                    color.float_by_name("s"),
+                    color.float_by_name("v"),
+                    1.0,
+                    rect.width(),
+                    rect.height(),
+                    0.0,
+                    0.0,
+                    m0,
+                    m1,
+                    m2,
+                    1.0,
+                    p0,
+                    p1,
+                    p2,
+                    p3,
+                ],
+            },
+        ));
+    }
+
+    fn take_error(&self) -> Option<String> {
+        self.failed.lock().unwrap().error.take()
+    }
+}
+
+struct WgpuGradients {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    target_format: wgpu::TextureFormat,
+    gradients: HashMap<GradientType, GradientResources>,
+}
+
+struct GradientResources {
+    pipeline: wgpu::RenderPipeline,
+    uniform: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl WgpuGradients {
+    // Validation errors are scoped, otherwise wgpu's default handler
+    // panics on them.
+    fn insert(&mut self, device: &wgpu::Device, gtype: &GradientType) -> Result<(), String> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("gradient"),
+            source: wgpu::ShaderSource::Wgsl(get_shader_source(gtype).into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("gradient"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.target_format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        if let Some(err) = pollster::block_on(device.pop_error_scope()) {
+            return Err(format!("Failed to create pipeline for {gtype:?}: {err}"));
+        }
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gradient"),
+            size: 80,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gradient"),
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.as_entire_binding(),
+            }],
+        });
+        self.gradients.insert(
+            gtype.clone(),
+            GradientResources {
+                pipeline,
+                uniform,
+                bind_group,
+            },
+        );
+        Ok(())
+    }
+}
+
+// Laid out like the `Uniforms` struct in the shaders: color, hsv, size,
+// model and params, each padded to a vec4.
+struct GradientCallback {
+    gtype: GradientType,
+    failed: Arc<Mutex<Failed>>,
+    ctx: egui::Context,
+    uniforms: [f32; 20],
+}
+
+impl egui_wgpu::CallbackTrait for GradientCallback {
+    fn prepare(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        _screen_descriptor: &egui_wgpu::ScreenDescriptor,
+        _egui_encoder: &mut wgpu::CommandEncoder,
+        callback_resources: &mut egui_wgpu::CallbackResources,
+    ) -> Vec<wgpu::CommandBuffer> {
+        let resources: &mut WgpuGradients = callback_resources.get_mut().unwrap();
+        let mut failed = self.failed.lock().unwrap();
+        if !resources.gradients.contains_key(&self.gtype) && !failed.gtypes.contains(&self.gtype) {
+            if let Err(err) = resources.insert(device, &self.gtype) {
+                failed.gtypes.insert(self.gtype.clone());
+                failed.error = Some(err);
+                self.ctx.request_repaint();
+            }
+        }
+        if let Some(gradient) = resources.gradients.get(&self.gtype) {
+            let bytes: Vec<u8> = self.uniforms.iter().flat_map(|c| c.to_ne_bytes()).collect();
+            queue.write_buffer(&gradient.uniform, 0, &bytes);
+        }
+        Vec::new()
+    }
+
+    fn paint<'a>(
+        &'a self,
+        _info: egui::PaintCallbackInfo,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        callback_resources: &'a egui_wgpu::CallbackResources,
+    ) {
+        let resources: &WgpuGradients = callback_resources.get().unwrap();
+        if let Some(gradient) = resources.gradients.get(&self.gtype) {
+            render_pass.set_pipeline(&gradient.pipeline);
+            render_pass.set_bind_group(0, &gradient.bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+}
+
+// WGSL ports of `gradient::get_shader_sources`.
+fn get_shader_source(gtype: &GradientType) -> String {
+    let constants = format!(
+        r#"
+            const TAU: f32 = 6.28318530718;
+            const RING_WIDTH: f32 = {RING_WIDTH:?};
+            const STRIP_WIDTH: f32 = {STRIP_WIDTH:?};
+            const STRIP_GAP: f32 = {STRIP_GAP:?};
+        "#
+    );
+    let vertex_shader_source = r#"
+            struct VertexOutput {
+                @builtin(position) position: vec4<f32>,
+                @location(0) tex_coord: vec2<f32>,
+            };
+
+            struct Uniforms {
+                color: vec4<f32>,
+                hsv: vec4<f32>,
+                size: vec4<f32>,
+                model: vec4<f32>,
+                params: vec4<f32>,
+            };
+
+            @group(0) @binding(0) var<uniform> u: Uniforms;
+
+            @vertex
+            fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+                var verts = array<vec2<f32>, 6>(
+                    vec2<f32>(-1.0, 1.0),
+                    vec2<f32>(1.0, 1.0),
+                    vec2<f32>(1.0, -1.0),
+                    vec2<f32>(1.0, -1.0),
+                    vec2<f32>(-1.0, 1.0),
+                    vec2<f32>(-1.0, -1.0)
+                );
+                var out: VertexOutput;
+                out.position = vec4<f32>(verts[index], 0.0, 1.0);
+                out.tex_coord = verts[index] * 0.5 + 0.5;
+                return out;
+            }
+        "#;
+
+    let hsv2rgb = r#"
+            fn hsv2rgb(h: f32, s: f32, v: f32, a: f32) -> vec4<f32> {
+                let c = v * s;
+                let x = c * (1.0 - abs((h * 6.0) % 2.0 - 1.0));
+                let m = v - c;
+
+                var rgb: vec3<f32>;
+
+                if (h < 1.0/6.0) {
+                    rgb = vec3<f32>(c, x, 0.0);
+                } else if (h < 2.0/6.0) {
+                    rgb = vec3<f32>(x, c, 0.0);
+                } else if (h < 3.0/6.0) {
+                    rgb = vec3<f32>(0.0, c, x);
+                } else if (h < 4.0/6.0) {
+                    rgb = vec3<f32>(0.0, x, c);
+                } else if (h < 5.0/6.0) {
+                    rgb = vec3<f32>(x, 0.0, c);
+                } else {
+                    rgb = vec3<f32>(c, 0.0, x);
+                }
+
+                return vec4<f32>(rgb + vec3<f32>(m), a);
+            }
+        "#;
+    let rgb2hsv = r#"
+            fn rgb2hsv(r: f32, g: f32, b: f32, a: f32) -> vec4<f32> {
+                let cmax = max(r, max(g, b));
+                let cmin = min(r, min(g, b));
+                let delta = cmax - cmin;
+
+                var h = 0.0;
+                if (delta != 0.0) {
+                    if (cmax == r) {
+                        h = ((g - b) / delta + 6.0) % 6.0 / 6.0;
+                    } else if (cmax == g) {
+                        h = ((b - r) / delta + 2.0) / 6.0;
+                    } else {
+                        h = ((r - g) / delta + 4.0) / 6.0;
+                    }
+                }
+                var s = 0.0;
+                if (cmax != 0.0) {
+                    s = delta / cmax;
+                }
+
+                return vec4<f32>(h, s, cmax, a);
+            }
+        "#;
+    let hsl_cmyk = r#"
+            fn hsv2hsl(s: f32, v: f32) -> vec2<f32> {
+                let l = v * (1.0 - s * 0.5);
+                var sl = 0.0;
+                if (l > 0.0 && l < 1.0) {
+                    sl = (v - l) / min(l, 1.0 - l);
+                }
+                return vec2<f32>(sl, l);
+            }
+
+            fn hsl2rgb(h: f32, s: f32, l: f32, a: f32) -> vec4<f32> {
+                let v = l + s * min(l, 1.0 - l);
+                var sv = 0.0;
+                if (v > 0.0) {
+                    sv = 2.0 * (1.0 - l / v);
+                }
+                return hsv2rgb(h, sv, v, a);
+            }
+
+            fn rgb2cmyk(rgb: vec3<f32>) -> vec4<f32> {
+                let k = 1.0 - max(rgb.r, max(rgb.g, rgb.b));
+                if (k >= 1.0) {
+                    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+                }
+                return vec4<f32>((vec3<f32>(1.0) - rgb - vec3<f32>(k)) / (1.0 - k), k);
+            }
+
+            fn cmyk2rgb(cmyk: vec4<f32>) -> vec4<f32> {
+                return vec4<f32>((vec3<f32>(1.0) - cmyk.rgb) * (1.0 - cmyk.a), 1.0);
+            }
+        "#;
+
+    // Mirrors the `shade`/`main` split of the GLSL shaders.
+    let finish = r#"
+            fn q(x: f32, steps: f32) -> f32 {
+                if (u.params.y > 0.5) {
+                    return round(x * steps) / steps;
+                }
+                return x;
+            }
+
+            fn dither_noise(p: vec2<f32>) -> f32 {
+                if (u.params.x < 1.5) {
+                    let p = vec2<u32>(p) & vec2<u32>(7u);
+                    let xy = p.x ^ p.y;
+                    let v = ((xy & 1u) << 5u) | ((p.y & 1u) << 4u) | ((xy & 2u) << 2u)
+                        | ((p.y & 2u) << 1u) | ((xy & 4u) >> 1u) | ((p.y & 4u) >> 2u);
+                    return (f32(v) + 0.5) / 64.0;
+                }
+                return fract(52.9829189 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+            }
+
+            @fragment
+            fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+                let color = shade(in);
+                if (u.params.x > 0.5 && u.params.y < 0.5) {
+                    let n = dither_noise(floor(in.position.xy)) - 0.5;
+                    return vec4<f32>(color.rgb + n * color.a / 255.0, color.a);
+                }
+                return color;
+            }
+        "#;
+
+    let plane_source = match gtype {
+        GradientType::Gradient(Shape::Plane(plane)) => plane_shader(plane),
+        _ => String::new(),
+    };
+    let slider_source;
+    let fragment_shader_source = match gtype {
+        GradientType::Gradient(shape) => match shape {
+            Shape::Plane(_) => &plane_source,
+            Shape::Triangle => {
+                r#"
+                fn shade(in: VertexOutput) -> vec4<f32> {
+                    let size = u.size.xy;
+                    let pt = in.tex_coord * size;
+                    let center = size * 0.5;
+                    let outer = min(size.x, size.y) * 0.5;
+                    let inner = outer * (1.0 - RING_WIDTH);
+                    let d = pt - center;
+                    let dist = length(d);
+                    if (dist >= inner) {
+                        let h = q(fract(atan2(d.y, d.x) / TAU + 1.0), 360.0);
+                        let edge = clamp(min(outer - dist, dist - inner) + 0.5, 0.0, 1.0);
+                        return hsv2rgb(h, 1.0, 1.0, 1.0) * edge;
+                    }
+
+                    let angle = u.hsv.x * TAU;
+                    let a = center + inner * vec2<f32>(cos(angle), sin(angle));
+                    let b = center + inner * vec2<f32>(cos(angle + TAU / 3.0), sin(angle + TAU / 3.0));
+                    let c = center + inner * vec2<f32>(cos(angle + TAU * 2.0 / 3.0), sin(angle + TAU * 2.0 / 3.0));
+                    let v0 = b - a;
+                    let v1 = c - a;
+                    let v2 = pt - a;
+                    let d00 = dot(v0, v0);
+                    let d01 = dot(v0, v1);
+                    let d11 = dot(v1, v1);
+                    let d20 = dot(v2, v0);
+                    let d21 = dot(v2, v1);
+                    let denom = d00 * d11 - d01 * d01;
+                    let wb = (d11 * d20 - d01 * d21) / denom;
+                    let wc = (d00 * d21 - d01 * d20) / denom;
+                    let wa = 1.0 - wb - wc;
+
+                    let edge = clamp(min(wa, min(wb, wc)) * 1.5 * inner + 0.5, 0.0, 1.0);
+                    let v = max(wa, 0.0) + max(wb, 0.0);
+                    var s = 0.0;
+                    if (v > 0.0) {
+                        s = max(wa, 0.0) / v;
+                    }
+                    return hsv2rgb(u.hsv.x, q(s, 100.0), q(v, 100.0), 1.0) * edge;
+                }
+            "#
+            }
+            Shape::Wheel => {
+                r#"
+                fn shade(in: VertexOutput) -> vec4<f32> {
+                    let size = u.size.xy;
+                    let pt = in.tex_coord * size;
+                    if (pt.x >= size.x - STRIP_WIDTH) {
+                        return hsv2rgb(u.hsv.x, u.hsv.y, q(in.tex_coord.y, 100.0), 1.0);
+                    }
+
+                    let width = size.x - STRIP_WIDTH - STRIP_GAP;
+                    let radius = min(width, size.y) * 0.5;
+                    let d = pt - vec2<f32>(width * 0.5, size.y * 0.5);
+                    let dist = length(d);
+                    let h = q(fract(atan2(d.y, d.x) / TAU + 1.0), 360.0);
+                    let s = q(min(dist / radius, 1.0), 100.0);
+                    let edge = clamp(radius - dist + 0.5, 0.0, 1.0);
+                    return hsv2rgb(h, s, u.hsv.z, 1.0) * edge;
+                }
+            "#
+            }
+        },
+        GradientType::Slider(stype) => {
+            let body = match stype.as_str() {
+                "r" => "return vec4<f32>(x, u.color.g, u.color.b, 1.0);",
+                "g" => "return vec4<f32>(u.color.r, x, u.color.b, 1.0);",
+                "b" => "return vec4<f32>(u.color.r, u.color.g, x, 1.0);",
+                "h" => "return hsv2rgb(x, 1.0, 1.0, 1.0);",
+                "s" => {
+                    "let hsv = rgb2hsv(u.color.r, u.color.g, u.color.b, u.color.a);
+                    return hsv2rgb(hsv.r, x, hsv.b, hsv.a);"
+                }
+                "v" => {
+                    "let hsv = rgb2hsv(u.color.r, u.color.g, u.color.b, u.color.a);
+                    return hsv2rgb(hsv.r, hsv.g, x, hsv.a);"
+                }
+                "sl" => {
+                    "let hsv = rgb2hsv(u.color.r, u.color.g, u.color.b, u.color.a);
+                    let hsl = hsv2hsl(hsv.g, hsv.b);
+                    return hsl2rgb(hsv.r, x, hsl.y, 1.0);"
+                }
+                "l" => {
+                    "let hsv = rgb2hsv(u.color.r, u.color.g, u.color.b, u.color.a);
+                    let hsl = hsv2hsl(hsv.g, hsv.b);
+                    return hsl2rgb(hsv.r, hsl.x, x, 1.0);"
+                }
+                "c" => "var cmyk = rgb2cmyk(u.color.rgb); cmyk.x = x; return cmyk2rgb(cmyk);",
+                "m" => "var cmyk = rgb2cmyk(u.color.rgb); cmyk.y = x; return cmyk2rgb(cmyk);",
+                "y" => "var cmyk = rgb2cmyk(u.color.rgb); cmyk.z = x; return cmyk2rgb(cmyk);",
+                "k" => "var cmyk = rgb2cmyk(u.color.rgb); cmyk.w = x; return cmyk2rgb(cmyk);",
+                _ => "return u.color;",
+            };
+            let max = max_by_name(stype) as f32;
+            slider_source = format!(
+                "fn shade(in: VertexOutput) -> vec4<f32> {{
+                    let x = q(in.tex_coord.x, {max:?});
+                    {body}
+                }}"
+            );
+            &slider_source
+        }
+    };
+    format!("{constants}\n{vertex_shader_source}\n{hsv2rgb}\n{rgb2hsv}\n{hsl_cmyk}\n{finish}\n{fragment_shader_source}")
+}
+
+// WGSL port of `gradient::plane_shader`.
+fn plane_shader(plane: &Plane) -> String {
+    let maxes = plane.model.maxes().map(|max| max as f32);
+    let to_rgb = match plane.model {
+        Model::Rgb => "return m;",
+        Model::Hsv => "return hsv2rgb(m.x, m.y, m.z, 1.0).rgb;",
+        Model::Hsl => {
+            r#"
+                    let v = m.z + m.y * min(m.z, 1.0 - m.z);
+                    var s = 0.0;
+                    if (v > 0.0) {
+                        s = 2.0 * (1.0 - m.z / v);
+                    }
+                    return hsv2rgb(m.x, s, v, 1.0).rgb;
+                "#
+        }
+        Model::Oklch => {
+            r#"
+                    let chroma = m.y * OKLCH_MAX_CHROMA;
+                    let a = chroma * cos(m.z * TAU);
+                    let b = chroma * sin(m.z * TAU);
+                    let l = pow3(m.x + 0.3963377774 * a + 0.2158037573 * b);
+                    let mm = pow3(m.x - 0.1055613458 * a - 0.0638541728 * b);
+                    let s = pow3(m.x - 0.0894841775 * a - 1.2914855480 * b);
+                    let linear = vec3<f32>(
+                        4.0767416621 * l - 3.3077115913 * mm + 0.2309699292 * s,
+                        -1.2684380046 * l + 2.6097574011 * mm - 0.3413193965 * s,
+                        -0.0041960863 * l - 0.7034186147 * mm + 1.7076147010 * s
+                    );
+                    let low = linear * 12.92;
+                    let high = 1.055 * pow(max(linear, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.4)) - 0.055;
+                    return select(high, low, linear <= vec3<f32>(0.0031308));
+                "#
+        }
+    };
+    format!(
+        r#"
+                const OKLCH_MAX_CHROMA: f32 = {OKLCH_MAX_CHROMA:?};
+                const OUT_OF_GAMUT: vec3<f32> = vec3<f32>(0.15);
+                const X: i32 = {x};
+                const Y: i32 = {y};
+                const Z: i32 = {z};
+                const MAX_X: f32 = {max_x:?};
+                const MAX_Y: f32 = {max_y:?};
+                const MAX_Z: f32 = {max_z:?};
+                const STRIP: bool = {strip};
+
+                fn pow3(x: f32) -> f32 {{
+                    return x * x * x;
+                }}
+
+                fn to_rgb(m: vec3<f32>) -> vec3<f32> {{
+                    {to_rgb}
+                }}
+
+                fn plane_color(m: vec3<f32>) -> vec4<f32> {{
+                    let rgb = to_rgb(m);
+                    if (any(rgb < vec3<f32>(-0.001)) || any(rgb > vec3<f32>(1.001))) {{
+                        return vec4<f32>(OUT_OF_GAMUT, 1.0);
+                    }}
+                    return vec4<f32>(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+                }}
+
+                fn shade(in: VertexOutput) -> vec4<f32> {{
+                    let size = u.size.xy;
+                    var m = u.model.xyz;
+                    let pt = in.tex_coord * size;
+                    var width = size.x;
+                    if (STRIP) {{
+                        if (pt.x >= size.x - STRIP_WIDTH) {{
+                            m[Z] = q(in.tex_coord.y, MAX_Z);
+                            return plane_color(m);
+                        }}
+                        width = size.x - STRIP_WIDTH - STRIP_GAP;
+                        if (pt.x > width) {{
+                            return vec4<f32>(0.0);
+                        }}
+                    }}
+                    m[X] = q(pt.x / width, MAX_X);
+                    m[Y] = q(in.tex_coord.y, MAX_Y);
+                    return plane_color(m);
+                }}
+            "#,
+        x = plane.x,
+        y = plane.y,
+        z = plane.z(),
+        max_x = maxes[plane.x],
+        max_y = maxes[plane.y],
+        max_z = maxes[plane.z()],
+        strip = plane.strip,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+    use super::*;
+    use crate::app::SLIDER_LABELS;
+
+    // Every shape, every plane each model can make, and all sliders.
+    fn gradient_types() -> Vec<GradientType> {
+        let mut gtypes: Vec<GradientType> = Shape::ALL.map(GradientType::Gradient).into();
+        for model in Model::ALL {
+            for (x, y) in [(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)] {
+                for strip in [false, true] {
+                    let plane = Plane { model, x, y, strip };
+                    gtypes.push(GradientType::Gradient(Shape::Plane(plane)));
+                }
+            }
+        }
+        gtypes.extend(SLIDER_LABELS.map(|label| GradientType::Slider(label.to_string())));
+        gtypes
+    }
+
+    #[test]
+    fn shaders_validate() {
+        let mut validator = Validator::new(ValidationFlags::all(), Capabilities::empty());
+        for gtype in gradient_types() {
+            let source = get_shader_source(&gtype);
+            let module = naga::front::wgsl::parse_str(&source)
+                .unwrap_or_else(|err| panic!("{gtype:?}:\n{}", err.emit_to_string(&source)));
+            if let Err(err) = validator.validate(&module) {
+                panic!("{gtype:?}:\n{}", err.emit_to_string(&source));
+            }
+        }
+    }
+}